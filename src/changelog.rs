@@ -0,0 +1,197 @@
+use crate::{
+    download::api_clients,
+    manifest::Manifest,
+    metadata,
+    structs::{ModId, ResolvedFile},
+};
+use anyhow::Result;
+use libium::config::structs::ModLoader;
+use log::warn;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// The changelogs of a project between the installed file and the newly resolved one
+#[derive(Serialize, Debug)]
+pub struct Changelog {
+    pub name: String,
+    pub id: ModId,
+    /// The installed version
+    pub from: String,
+    /// The newly resolved version
+    pub to: String,
+    pub versions: Vec<VersionChangelog>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VersionChangelog {
+    pub version: String,
+    pub changelog: String,
+}
+
+impl Changelog {
+    /// Format the changelog as a markdown section
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("## {} ({} -> {})\n", self.name, self.from, self.to);
+        for version in self.versions.iter() {
+            let _ = write!(
+                markdown,
+                "\n### {}\n\n{}\n",
+                version.version,
+                version.changelog.trim()
+            );
+        }
+        markdown
+    }
+}
+
+/// Get the changelogs of the versions released after the installed file of each project in the `manifest`,
+/// up to the file resolved now, for the `resolved` files that replace an installed one
+/// Only versions for the `mc_version` and `loader` are included
+pub async fn get_changelogs(
+    manifest: &Manifest,
+    resolved: &[ResolvedFile],
+    mc_version: &str,
+    loader: &ModLoader,
+) -> Result<Vec<Changelog>> {
+    let (furse, ferinth) = api_clients()?;
+    let mut changelogs = Vec::new();
+    for resolved in resolved {
+        let installed = match manifest.get(&resolved.id) {
+            Some(installed) if installed.file_id != resolved.file_id => installed,
+            _ => continue,
+        };
+        let versions = match &resolved.id {
+            ModId::CurseForgeId(id) => {
+                let id = *id as i32;
+                let mut files = furse.get_mod_files(id).await?;
+                files.sort_by_key(|file| std::cmp::Reverse(file.file_date));
+                let date = |file_id: &str| {
+                    files
+                        .iter()
+                        .find(|file| file.id.to_string() == file_id)
+                        .map(|file| file.file_date)
+                };
+                let (from, to) = (date(&installed.file_id), date(&resolved.file_id));
+                let mut versions = Vec::new();
+                for file in files.iter().filter(|file| {
+                    to.is_some_and(|to| file.file_date <= to)
+                        && from.map_or(file.id.to_string() == resolved.file_id, |from| {
+                            file.file_date > from
+                        })
+                        && file.game_versions.iter().any(|v| v == mc_version)
+                        && for_loader(&file.game_versions, loader)
+                }) {
+                    versions.push(VersionChangelog {
+                        version: file.display_name.clone(),
+                        changelog: html_to_text(&furse.get_mod_file_changelog(id, file.id).await?),
+                    });
+                }
+                versions
+            }
+            ModId::ModrinthId(id) => {
+                let versions = ferinth.list_versions(id).await?;
+                let new = versions.iter().find(|v| v.id == resolved.file_id);
+                let from = versions
+                    .iter()
+                    .find(|v| v.id == installed.file_id)
+                    .map(|v| v.date_published);
+                versions
+                    .iter()
+                    .filter(|v| {
+                        new.is_some_and(|new| {
+                            v.date_published <= new.date_published
+                                && v.loaders.iter().any(|l| new.loaders.contains(l))
+                        }) && from.map_or(v.id == resolved.file_id, |from| v.date_published > from)
+                            && v.game_versions.iter().any(|g| g == mc_version)
+                    })
+                    .map(|v| VersionChangelog {
+                        version: v.version_number.clone(),
+                        changelog: v.changelog.clone().unwrap_or_default(),
+                    })
+                    .collect()
+            }
+        };
+        if versions.is_empty() {
+            warn!(
+                "No changelogs found for {} ({})",
+                resolved.name, resolved.id
+            );
+        }
+        changelogs.push(Changelog {
+            name: resolved.name.clone(),
+            id: resolved.id.clone(),
+            from: installed.version.clone(),
+            to: resolved.version.clone(),
+            versions,
+        });
+    }
+    Ok(changelogs)
+}
+
+/// Whether a CurseForge file with the `game_versions` is made for the `loader`
+/// Loaders are listed among the game versions, old files without any are assumed to match
+fn for_loader(game_versions: &[String], loader: &ModLoader) -> bool {
+    let loaders: Vec<ModLoader> = game_versions
+        .iter()
+        .filter_map(|v| ModLoader::try_from(v.to_lowercase().as_str()).ok())
+        .collect();
+    metadata::loader_supported(&loaders, loader)
+}
+
+/// Convert the HTML changelogs of CurseForge to plain text
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim_start_matches('/');
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        match name.to_lowercase().as_str() {
+            // Empty lines are removed below
+            "br" | "br/" | "p" | "div" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4" => text.push('\n'),
+            "li" if !rest[start + 1..].starts_with('/') => text.push_str("\n- "),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn filters_curseforge_loaders() {
+    let versions = |versions: &[&str]| versions.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    assert!(!for_loader(
+        &versions(&["1.20.1", "Forge"]),
+        &ModLoader::Fabric
+    ));
+    assert!(for_loader(
+        &versions(&["1.20.1", "Fabric"]),
+        &ModLoader::Quilt
+    ));
+    assert!(for_loader(&versions(&["1.20.1"]), &ModLoader::Fabric));
+}
+
+#[test]
+fn converts_html_changelogs() {
+    assert_eq!(
+        html_to_text(
+            "<p>Fixed <b>crash</b> &amp; lag</p><ul><li>Item one</li><li>Item two</li></ul>"
+        ),
+        "Fixed crash & lag\n- Item one\n- Item two"
+    );
+}
@@ -1,27 +1,84 @@
 use crate::{
-    config::{Config, PathOrUrl},
-    download,
-    structs::ModSide,
+    changelog,
+    config::{Config, PathOrUrl, Profile, DEFAULT_PROFILE},
+    diff,
+    download::{self, ProjectField},
+    errors::BreezeError,
+    init, manifest, options,
+    output::{self, Dependency, Event, OutputFormat},
+    structs::{ModId, ModSide, Pack, ResolvedFile},
 };
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use indicatif::{ProgressBar, ProgressStyle};
-use log::info;
+use itertools::Itertools;
+use libium::config::structs::ModLoader;
+use log::{info, warn};
 use promptly::prompt;
-use reqwest::header::CONTENT_TYPE;
-use std::{fs, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 use url::Url;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
-struct Cli {
+pub struct Cli {
     #[clap(subcommand)]
     command: Commands,
+    /// Profile to use instead of the active one
+    #[clap(long, global = true, value_name = "PROFILE")]
+    profile: Option<String>,
+    /// Show more log messages in the console, pass twice to show debug messages
+    #[clap(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+    /// Only show errors in the console
+    #[clap(short, long, global = true)]
+    pub quiet: bool,
+    /// Format of the log messages
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    pub log_format: LogFormat,
+    /// Format of the results, JSON prints one event per line and never prompts
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    pub output: OutputFormat,
+    /// Fail instead of prompting and log progress instead of showing spinners,
+    /// the default when stdin isn't a terminal
+    #[clap(long, global = true)]
+    pub non_interactive: bool,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Manage profiles, each with their own Minecraft directory, source, side and options
+    Profile {
+        #[clap(subcommand)]
+        action: ProfileAction,
+    },
     /// Source a file or url as a modpack
     Source {
         /// TOML file with modpack definition
@@ -46,6 +103,14 @@ enum Commands {
             value_name = "SIDE"
         )]
         side: Option<ModSide>,
+        #[clap(flatten)]
+        content: ContentArgs,
+        /// Worlds to install datapacks into, instead of the global datapack folder of the pack
+        #[clap(long, value_name = "WORLD", value_delimiter = ',')]
+        worlds: Vec<String>,
+        /// Install datapacks into the global datapack folder of the pack again
+        #[clap(long, conflicts_with = "worlds")]
+        no_worlds: bool,
     },
     /// Upgrade mods
     Upgrade {
@@ -68,50 +133,368 @@ enum Commands {
         /// Minecraft root directory
         #[clap(short, long, value_parser, value_name = "DIR")]
         dir: Option<PathBuf>,
-        /// Whether to download resourcepacks
+        #[clap(flatten)]
+        content: ContentArgs,
+        /// Worlds to install datapacks into, instead of the global datapack folder of the pack
+        #[clap(long, value_name = "WORLD", value_delimiter = ',')]
+        worlds: Vec<String>,
+        /// Install datapacks into the global datapack folder of the pack again
+        #[clap(long, conflicts_with = "worlds")]
+        no_worlds: bool,
+        /// Enable optional features of the pack
+        #[clap(long, value_name = "FEATURE", value_delimiter = ',')]
+        with: Vec<String>,
+        /// Disable optional features of the pack
+        #[clap(long, value_name = "FEATURE", value_delimiter = ',')]
+        without: Vec<String>,
+        /// Show the changelogs of the files that are replaced
+        #[clap(long)]
+        changelog: bool,
+        /// Also write the changelogs to CHANGELOG-<DATE>.md in the Minecraft directory
         #[clap(long)]
-        resourcepacks: bool,
-        /// Whether to download shaderpacks
+        changelog_file: bool,
+    },
+    /// List the installed files that have a newer version, without downloading anything
+    Outdated {
+        /// Which types of mods to check
+        #[clap(
+            short,
+            long,
+            value_parser,
+            value_enum,
+            ignore_case = true,
+            value_name = "SIDE"
+        )]
+        side: Option<ModSide>,
+        /// TOML file with modpack definition
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// URL to TOML with modpack definition
+        #[clap(short, long, value_parser, value_name = "URL")]
+        url: Option<Url>,
+        #[clap(flatten)]
+        content: ContentArgs,
+    },
+    /// Generate a pack TOML from the jars in an existing mods folder
+    Init {
+        /// The mods folder to identify the jars of
+        #[clap(long, value_name = "DIR")]
+        from_dir: PathBuf,
+        /// The pack TOML to write
+        #[clap(short, long, value_name = "FILE", default_value = "pack.toml")]
+        file: PathBuf,
+        /// The name of the pack, the name of the Minecraft directory by default
+        #[clap(short, long)]
+        name: Option<String>,
+    },
+    /// Compare two versions of a pack
+    Diff {
+        /// The old version, as a file, URL or git revision and path like `HEAD~1:pack.toml`
+        old: String,
+        /// The new version, as a file, URL or git revision and path like `HEAD:pack.toml`
+        new: String,
+        /// Also compare the files resolved for both versions
+        #[clap(long)]
+        resolve: bool,
+        /// Which types of mods to resolve
+        #[clap(
+            short,
+            long,
+            value_parser,
+            value_enum,
+            ignore_case = true,
+            value_name = "SIDE"
+        )]
+        side: Option<ModSide>,
+        #[clap(flatten)]
+        content: ContentArgs,
+    },
+    /// Resolve the pack and report problems without downloading anything
+    Check {
+        /// Which types of mods to check
+        #[clap(
+            short,
+            long,
+            value_parser,
+            value_enum,
+            ignore_case = true,
+            value_name = "SIDE"
+        )]
+        side: Option<ModSide>,
+        /// TOML file with modpack definition
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// URL to TOML with modpack definition
+        #[clap(short, long, value_parser, value_name = "URL")]
+        url: Option<Url>,
+        #[clap(flatten)]
+        content: ContentArgs,
+        /// List the optional and embedded dependencies of the resolved files
         #[clap(long)]
-        shaderpacks: bool,
+        optional: bool,
+        /// Enable optional features of the pack
+        #[clap(long, value_name = "FEATURE", value_delimiter = ',')]
+        with: Vec<String>,
+        /// Disable optional features of the pack
+        #[clap(long, value_name = "FEATURE", value_delimiter = ',')]
+        without: Vec<String>,
+    },
+    /// Check a pack for mistakes without resolving its mods
+    Validate {
+        /// TOML file with modpack definition
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// URL to TOML with modpack definition
+        #[clap(short, long, value_parser, value_name = "URL")]
+        url: Option<Url>,
+    },
+    /// Print the JSON Schema of the pack format, for editors to validate and complete packs with
+    Schema {
+        /// Write the schema to a file instead
+        #[clap(short, long, value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// Add a profile
+    Add {
+        name: String,
+        /// Minecraft root directory
+        #[clap(short, long, value_parser, value_name = "DIR")]
+        dir: Option<PathBuf>,
+        /// TOML file with modpack definition
+        #[clap(short, long, value_parser, conflicts_with = "url", value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// URL to TOML with modpack definition
+        #[clap(short, long, value_parser, conflicts_with = "file", value_name = "URL")]
+        url: Option<Url>,
+        /// Which types of mods to download
+        #[clap(
+            short,
+            long,
+            value_parser,
+            value_enum,
+            ignore_case = true,
+            value_name = "SIDE"
+        )]
+        side: Option<ModSide>,
     },
+    /// Use a profile when no `--profile` is passed
+    Use { name: String },
+    /// List the profiles
+    List,
+    /// Remove a profile
+    Remove { name: String },
 }
 
-pub async fn cli(config: &mut Config) -> Result<()> {
-    let cli = Cli::parse();
+/// Which types of content to download besides mods
+/// The choices are saved by `config` and `upgrade`, other commands only use them once
+#[derive(Args)]
+struct ContentArgs {
+    /// Download resourcepacks
+    #[clap(long, overrides_with = "no_resourcepacks")]
+    resourcepacks: bool,
+    /// Don't download resourcepacks
+    #[clap(long, overrides_with = "resourcepacks")]
+    no_resourcepacks: bool,
+    /// Download shaderpacks
+    #[clap(long, overrides_with = "no_shaderpacks")]
+    shaderpacks: bool,
+    /// Don't download shaderpacks
+    #[clap(long, overrides_with = "shaderpacks")]
+    no_shaderpacks: bool,
+    /// Download datapacks
+    #[clap(long, overrides_with = "no_datapacks")]
+    datapacks: bool,
+    /// Don't download datapacks
+    #[clap(long, overrides_with = "datapacks")]
+    no_datapacks: bool,
+}
 
+impl ContentArgs {
+    /// The content types that were turned on or off, in the order resourcepacks, shaderpacks, datapacks
+    fn toggles(&self) -> [Option<bool>; 3] {
+        let toggle = |on: bool, off: bool| (on || off).then_some(on);
+        [
+            toggle(self.resourcepacks, self.no_resourcepacks),
+            toggle(self.shaderpacks, self.no_shaderpacks),
+            toggle(self.datapacks, self.no_datapacks),
+        ]
+    }
+
+    /// Save the content types that were turned on or off in the `profile`
+    fn save(&self, profile: &mut Profile) {
+        let [resourcepacks, shaderpacks, datapacks] = self.toggles();
+        profile.resourcepacks = resourcepacks.or(profile.resourcepacks);
+        profile.shaderpacks = shaderpacks.or(profile.shaderpacks);
+        profile.datapacks = datapacks.or(profile.datapacks);
+    }
+
+    /// Whether to download resourcepacks, shaderpacks and datapacks, falling back to the `profile`
+    fn resolve(&self, profile: &Profile) -> (bool, bool, bool) {
+        let [resourcepacks, shaderpacks, datapacks] = self.toggles();
+        (
+            resourcepacks.or(profile.resourcepacks).unwrap_or_default(),
+            shaderpacks.or(profile.shaderpacks).unwrap_or_default(),
+            datapacks.or(profile.datapacks).unwrap_or_default(),
+        )
+    }
+}
+
+/// Save the `worlds` to install datapacks into in the `profile`, or clear them if `no_worlds` is set
+fn save_worlds(profile: &mut Profile, worlds: Vec<String>, no_worlds: bool) {
+    if no_worlds {
+        profile.worlds.clear();
+    } else if !worlds.is_empty() {
+        profile.worlds = worlds;
+    }
+}
+
+pub async fn cli(cli: Cli, config: &mut Config) -> Result<()> {
     match cli.command {
+        Commands::Profile { action } => manage_profiles(config, action).await,
+        command => {
+            let name = cli
+                .profile
+                .or_else(|| config.profile.clone())
+                .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+            let profile = config
+                .profile_mut(&name)
+                .ok_or(CliError::ProfileNotFound(name))?;
+            run(command, profile).await
+        }
+    }
+}
+
+async fn manage_profiles(config: &mut Config, action: ProfileAction) -> Result<()> {
+    match action {
+        ProfileAction::Add {
+            name,
+            dir,
+            file,
+            url,
+            side,
+        } => {
+            if config.profile_mut(&name).is_some() {
+                return Err(CliError::ProfileExists(name).into());
+            }
+            let mut profile = Profile {
+                source: get_source(file, url)?,
+                side,
+                ..Default::default()
+            };
+            if let Some(dir) = dir {
+                tokio::fs::create_dir_all(&dir).await?;
+                profile.mc_dir = Some(fs::canonicalize(dir)?);
+            }
+            info!("Adding profile {}", name);
+            config.profiles.insert(name, profile);
+        }
+        ProfileAction::Use { name } => {
+            if config.profile_mut(&name).is_none() {
+                return Err(CliError::ProfileNotFound(name).into());
+            }
+            info!("Using profile {}", name);
+            config.profile = (name != DEFAULT_PROFILE).then_some(name);
+        }
+        ProfileAction::List => {
+            let active = config.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+            let profiles = std::iter::once((DEFAULT_PROFILE, &config.default)).chain(
+                config
+                    .profiles
+                    .iter()
+                    .map(|(name, profile)| (name.as_str(), profile)),
+            );
+            for (name, profile) in profiles {
+                output::print(
+                    Event::Profile {
+                        name,
+                        active: name == active,
+                        mc_dir: profile.mc_dir.as_deref(),
+                    },
+                    format_args!(
+                        "{} {}: {}, {}",
+                        if name == active { "*" } else { " " },
+                        name,
+                        profile
+                            .mc_dir
+                            .as_ref()
+                            .map(|dir| dir.display().to_string())
+                            .unwrap_or_else(|| String::from("no directory")),
+                        match &profile.source {
+                            Some(PathOrUrl::Path(path)) => path.display().to_string(),
+                            Some(PathOrUrl::Url(url)) => url.to_string(),
+                            None => String::from("no source"),
+                        }
+                    ),
+                );
+            }
+        }
+        ProfileAction::Remove { name } => {
+            if name == DEFAULT_PROFILE {
+                return Err(CliError::RemoveDefaultProfile.into());
+            }
+            if config.profiles.remove(&name).is_none() {
+                return Err(CliError::ProfileNotFound(name).into());
+            }
+            info!("Removing profile {}", name);
+            if config.profile.as_ref() == Some(&name) {
+                config.profile = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run(command: Commands, profile: &mut Profile) -> Result<()> {
+    match command {
+        Commands::Profile { .. } => unreachable!("profiles are managed before selecting one"),
         Commands::Source { file, url } => {
             if let Some(source) = get_source(file, url)? {
-                config.source = Some(source);
+                profile.source = Some(source);
             } else {
                 return Err(CliError::NoSourceSpecified.into());
             }
-            info!("Setting source to {:?}", config.source);
+            info!("Setting source to {:?}", profile.source);
         }
-        Commands::Config { dir, side } => {
+        Commands::Config {
+            dir,
+            side,
+            content,
+            worlds,
+            no_worlds,
+        } => {
             if let Some(dir) = dir {
                 tokio::fs::create_dir_all(&dir).await?;
-                config.mc_dir = Some(fs::canonicalize(dir)?);
+                profile.mc_dir = Some(fs::canonicalize(dir)?);
             }
             if let Some(side) = side {
-                config.side = Some(side);
+                profile.side = Some(side);
             }
+            content.save(profile);
+            save_worlds(profile, worlds, no_worlds);
         }
         Commands::Upgrade {
             side,
             file,
             url,
             dir,
-            resourcepacks,
-            shaderpacks,
+            content,
+            worlds,
+            no_worlds,
+            with,
+            without,
+            changelog,
+            changelog_file,
         } => {
             // Get TOML source
             let source: PathOrUrl = if let Some(source) = get_source(file, url)? {
-                config.source = Some(source.clone());
+                profile.source = Some(source.clone());
                 source
             } else {
-                if let Some(source) = config.source.clone() {
+                if let Some(source) = profile.source.clone() {
                     source
                 } else {
                     return Err(CliError::NoSourceSpecified.into());
@@ -121,77 +504,494 @@ pub async fn cli(config: &mut Config) -> Result<()> {
             let mc_dir = if let Some(dir) = dir {
                 info!("Setting Minecraft Directory: {:?}", dir);
                 tokio::fs::create_dir_all(&dir).await?;
-                config.mc_dir = Some(fs::canonicalize(&dir)?);
+                profile.mc_dir = Some(fs::canonicalize(&dir)?);
                 dir
             } else {
-                if let Some(mc_dir) = config.mc_dir.clone() {
+                if let Some(mc_dir) = profile.mc_dir.clone() {
                     info!("Found Minecraft Directory in Config: {:?}", mc_dir);
                     mc_dir
+                } else if !output::is_interactive() {
+                    return Err(CliError::NoModDirSpecified.into());
                 } else {
                     let dir: PathBuf = prompt("Minecraft Root Directory")?;
                     info!("Setting Minecraft Directory: {:?}", dir);
 
                     tokio::fs::create_dir_all(&dir).await?;
-                    config.mc_dir = Some(fs::canonicalize(&dir)?);
+                    profile.mc_dir = Some(fs::canonicalize(&dir)?);
                     dir
                 }
             };
             let side = if let Some(side) = side {
-                config.side = Some(side);
+                profile.side = Some(side);
                 side
             } else {
-                config.side.unwrap_or(ModSide::Client)
+                profile.side.unwrap_or(ModSide::Client)
             };
+            content.save(profile);
+            let (resourcepacks, shaderpacks, datapacks) = content.resolve(profile);
+            save_worlds(profile, worlds, no_worlds);
 
             let progress_bar = create_spinner("Parsing pack", "Finished parsing pack.");
-            // Get TOML contents
-            let toml = match source {
-                PathOrUrl::Path(path) => std::fs::read_to_string(path)?,
-                PathOrUrl::Url(url) => {
-                    let resp = reqwest::get(url.as_str()).await?;
-                    let content_type = resp.headers().get(CONTENT_TYPE);
-                    if let Some(ct) = content_type {
-                        if let Ok(ct) = ct.to_str() {
-                            if !ct.contains("text/plain") {
-                                return Err(CliError::NonPlainTextResponse(ct.to_string()).into());
-                            }
-                        }
-                    }
-                    resp.text().await?
-                }
-            };
-            let pack = crate::toml::parse(toml)?;
+            let mut pack = fetch_pack(source).await?;
+            info!("Parsed pack {}, version {}", pack.name, pack.version);
             progress_bar.finish();
+            update_selections(&mut profile.features, with, without);
+            select_features(&mut pack, &profile.features);
 
             let progress_bar = create_spinner("Fetching mods", "Finished fetching mods.");
+            let prefer = pack.prefer;
+            let on_incompatible = pack.on_incompatible;
+            let mut ordered: Vec<(i32, String)> = pack
+                .resourcepacks
+                .iter()
+                .filter_map(|r| r.order.map(|order| (order, r.name.clone())))
+                .collect();
+            ordered.sort_unstable();
+            let default_shader = pack.default_shader.clone();
+            let loader = pack.loader.clone();
+            let mc_version = pack.mc_version.clone();
+            let datapack_dirs = if datapacks {
+                get_datapack_dirs(&mc_dir, pack.global_datapacks.clone(), &profile.worlds)?
+            } else {
+                Vec::new()
+            };
             let mut to_download =
-                download::get_downloadables(side, resourcepacks, shaderpacks, pack).await?;
+                download::get_downloadables(side, resourcepacks, shaderpacks, datapacks, pack)
+                    .await?;
+            download::dedupe(&mut to_download, prefer);
+            let previous = manifest::read(&mc_dir)?.unwrap_or_default();
+            let mut duplicates = download::skip_duplicates(&mut to_download, &previous.duplicates);
+            let mut resolved = to_download.clone();
             progress_bar.finish();
+            resolved.iter().for_each(emit_resolved);
+            download::check_incompatibilities(&resolved, on_incompatible)?;
+            download::place_datapacks(&mut to_download, &datapack_dirs);
+            let mut installed = to_download.clone();
+            if changelog || changelog_file {
+                show_changelogs(&mc_dir, &resolved, &mc_version, &loader, changelog_file).await?;
+            }
 
             let progress_bar = create_spinner("Cleaning old mods", "Finished cleaning old mods.");
             download::clean(&mc_dir.join("mods"), &mut to_download, true).await?;
             download::clean(&mc_dir.join("resourcepacks"), &mut to_download, false).await?;
             download::clean(&mc_dir.join("shaderpacks"), &mut to_download, false).await?;
+            for dir in datapack_dirs.iter() {
+                tokio::fs::create_dir_all(mc_dir.join(dir)).await?;
+                download::clean(&mc_dir.join(dir), &mut to_download, false).await?;
+            }
             progress_bar.finish();
 
             if !to_download.is_empty() {
-                download::download(Arc::new(mc_dir), to_download).await?;
+                download::download(Arc::new(mc_dir.clone()), to_download).await?;
             } else {
                 info!("Already up to date.");
             }
+            let found = download::dedupe_jars(&mc_dir.join("mods"), &mut resolved, prefer).await?;
+            installed.retain(|i| !found.iter().any(|d| d.file_id == i.file_id));
+            duplicates.extend(found);
+            download::validate_jars(&mc_dir.join("mods"), &resolved, &loader, &mc_version);
+            download::clean_datapacks(&mc_dir, &datapack_dirs, &previous, &installed)?;
+            manifest::write(&mc_dir, &installed, duplicates)?;
+            // Resourcepacks of the last upgrade that were updated to a new file or removed from the pack
+            let mut updated: Vec<(String, String)> = Vec::new();
+            let mut removed: Vec<String> = Vec::new();
+            for file in previous.files.iter().filter(|file| {
+                file.path.starts_with("resourcepacks")
+                    && !installed.iter().any(|i| i.downloadable.output == file.path)
+            }) {
+                let Some(old) = file.path.file_name() else {
+                    continue;
+                };
+                let old = old.to_string_lossy().to_string();
+                match installed
+                    .iter()
+                    .find(|i| i.id == file.id && i.downloadable.output.starts_with("resourcepacks"))
+                {
+                    Some(new) => updated.push((old, new.downloadable.filename())),
+                    None => removed.push(old),
+                }
+            }
+            if resourcepacks && !(ordered.is_empty() && updated.is_empty() && removed.is_empty()) {
+                let filenames: Vec<String> = ordered
+                    .iter()
+                    .filter_map(|(_, name)| {
+                        resolved
+                            .iter()
+                            .find(|r| {
+                                &r.name == name
+                                    && r.downloadable.output.starts_with("resourcepacks")
+                            })
+                            .map(|r| r.downloadable.filename())
+                    })
+                    .collect();
+                options::set_resource_packs(&mc_dir, &filenames, &updated, &removed)?;
+            }
+            if let Some(default_shader) = default_shader.filter(|_| shaderpacks) {
+                match resolved.iter().find(|r| {
+                    r.name == default_shader && r.downloadable.output.starts_with("shaderpacks")
+                }) {
+                    Some(shader) => options::set_default_shader(
+                        &mc_dir,
+                        &loader,
+                        &shader.downloadable.filename(),
+                    )?,
+                    None => warn!("Default shader {} wasn't downloaded", default_shader),
+                }
+            }
+            output::emit(Event::Done {
+                files: resolved.len(),
+            });
+        }
+        Commands::Outdated {
+            side,
+            file,
+            url,
+            content,
+        } => {
+            let source = if let Some(source) = get_source(file, url)? {
+                source
+            } else if let Some(source) = profile.source.clone() {
+                source
+            } else {
+                return Err(CliError::NoSourceSpecified.into());
+            };
+            let mc_dir = profile.mc_dir.clone().ok_or(CliError::NoModDirSpecified)?;
+            let side = side.or(profile.side).unwrap_or(ModSide::Client);
+            let (resourcepacks, shaderpacks, datapacks) = content.resolve(profile);
+
+            let progress_bar = create_spinner("Parsing pack", "Finished parsing pack.");
+            let mut pack = fetch_pack(source).await?;
+            info!("Parsed pack {}, version {}", pack.name, pack.version);
+            progress_bar.finish();
+            select_features(&mut pack, &profile.features);
+
+            let progress_bar = create_spinner("Fetching mods", "Finished fetching mods.");
+            let prefer = pack.prefer;
+            let global_datapacks = pack.global_datapacks.clone();
+            let mut resolved =
+                download::get_downloadables(side, resourcepacks, shaderpacks, datapacks, pack)
+                    .await?;
+            download::dedupe(&mut resolved, prefer);
+            progress_bar.finish();
+
+            let manifest = manifest::read(&mc_dir)?;
+            match &manifest {
+                Some(manifest) => {
+                    download::skip_duplicates(&mut resolved, &manifest.duplicates);
+                }
+                None => warn!("The instance has no install manifest, comparing filenames instead"),
+            }
+            // Datapacks are compared where the upgrade places them
+            let datapack_dirs = if datapacks {
+                get_datapack_dirs(&mc_dir, global_datapacks, &profile.worlds)?
+            } else {
+                Vec::new()
+            };
+            let mut placed = resolved.clone();
+            download::place_datapacks(&mut placed, &datapack_dirs);
+            resolved.retain(|r| placed.iter().any(|p| p.id == r.id));
+            let mut outdated = Vec::new();
+            for r in resolved.iter() {
+                let installed = match &manifest {
+                    Some(manifest) => match manifest.get(&r.id) {
+                        Some(installed) if installed.file_id == r.file_id => continue,
+                        installed => installed.map(|i| i.version.as_str()),
+                    },
+                    None if placed
+                        .iter()
+                        .filter(|p| p.id == r.id)
+                        .all(|p| mc_dir.join(&p.downloadable.output).exists()) =>
+                    {
+                        continue
+                    }
+                    None => None,
+                };
+                outdated.push((r, installed));
+            }
+            if outdated.is_empty() {
+                output::print(Event::Done { files: 0 }, "Everything is up to date.");
+                return Ok(());
+            }
+            let ids: Vec<ModId> = outdated.iter().map(|(r, _)| r.id.clone()).collect();
+            let urls = download::get_project_fields(&ids, ProjectField::Url).await?;
+            let changelog = |r: &ResolvedFile| urls.get(&r.id).map(|url| r.changelog_url(url));
+            let width = |lengths: Vec<usize>, header: &str| {
+                lengths
+                    .into_iter()
+                    .max()
+                    .unwrap_or_default()
+                    .max(header.len())
+            };
+            let name_width = width(outdated.iter().map(|(r, _)| r.name.len()).collect(), "Mod");
+            let installed_width = width(
+                outdated
+                    .iter()
+                    .map(|(_, installed)| installed.unwrap_or(NOT_INSTALLED).len())
+                    .collect(),
+                "Installed",
+            );
+            let available_width = width(
+                outdated.iter().map(|(r, _)| r.version.len()).collect(),
+                "Available",
+            );
+            output::text(format_args!(
+                "{:name_width$}  {:installed_width$}  {:available_width$}  Changelog",
+                "Mod", "Installed", "Available"
+            ));
+            for (r, installed) in outdated.iter() {
+                output::print(
+                    Event::Outdated {
+                        name: &r.name,
+                        id: &r.id,
+                        installed: *installed,
+                        available: &r.version,
+                        changelog: changelog(r),
+                    },
+                    format_args!(
+                        "{:name_width$}  {:installed_width$}  {:available_width$}  {}",
+                        r.name,
+                        installed.unwrap_or(NOT_INSTALLED),
+                        r.version,
+                        changelog(r).unwrap_or_else(|| String::from("-"))
+                    ),
+                );
+            }
+            output::emit(Event::Done {
+                files: outdated.len(),
+            });
+        }
+        Commands::Init {
+            from_dir,
+            file,
+            name,
+        } => {
+            if file.exists() {
+                return Err(CliError::FileExists(file).into());
+            }
+            let from_dir = fs::canonicalize(from_dir)?;
+            let progress_bar = create_spinner("Identifying mods", "Finished identifying mods.");
+            let detected = init::detect(&from_dir).await?;
+            progress_bar.finish();
+            let incomplete = detected.loader.is_none() || detected.mc_version.is_none();
+            if incomplete {
+                warn!("The loader or Minecraft version couldn't be detected, the pack can't be used until they are filled in");
+            }
+            let name = name.unwrap_or_else(|| {
+                // The mods folder is usually in the Minecraft directory
+                from_dir
+                    .parent()
+                    .and_then(Path::file_name)
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| String::from("pack"))
+            });
+            fs::write(&file, init::generate_toml(&name, &detected))?;
+            for identified in detected.identified.iter() {
+                output::emit(Event::Identified {
+                    filename: &identified.filename,
+                    name: &identified.name,
+                    id: &identified.id,
+                });
+            }
+            for filename in detected.unknown.iter() {
+                output::print(
+                    Event::Unknown { filename },
+                    format_args!("Not found on Modrinth or CurseForge: {filename}"),
+                );
+            }
+            output::print(
+                Event::Done {
+                    files: detected.identified.len(),
+                },
+                format_args!(
+                    "Wrote {} mods to {:?}, {} jars weren't recognized.{}",
+                    detected.identified.len(),
+                    file,
+                    detected.unknown.len(),
+                    if incomplete {
+                        " Fill in the loader and Minecraft version before using it."
+                    } else {
+                        ""
+                    }
+                ),
+            );
+        }
+        Commands::Diff {
+            old,
+            new,
+            resolve,
+            side,
+            content,
+        } => {
+            let progress_bar = create_spinner("Parsing packs", "Finished parsing packs.");
+            let mut old = read_diff_source(&old).await?;
+            let mut new = read_diff_source(&new).await?;
+            progress_bar.finish();
+            let mut changes = diff::diff_packs(&old, &new);
+
+            if resolve {
+                let side = side.or(profile.side).unwrap_or(ModSide::Client);
+                let (resourcepacks, shaderpacks, datapacks) = content.resolve(profile);
+                select_features(&mut old, &profile.features);
+                select_features(&mut new, &profile.features);
+                let progress_bar = create_spinner("Fetching mods", "Finished fetching mods.");
+                let (old_prefer, new_prefer) = (old.prefer, new.prefer);
+                let mut old_files =
+                    download::get_downloadables(side, resourcepacks, shaderpacks, datapacks, old)
+                        .await?;
+                download::dedupe(&mut old_files, old_prefer);
+                let mut new_files =
+                    download::get_downloadables(side, resourcepacks, shaderpacks, datapacks, new)
+                        .await?;
+                download::dedupe(&mut new_files, new_prefer);
+                progress_bar.finish();
+                changes.extend(diff::diff_resolved(&old_files, &new_files));
+            }
+
+            if changes.is_empty() {
+                output::text("No differences.");
+            }
+            for change in changes.iter() {
+                output::print(Event::Diff(change), change);
+            }
+        }
+        Commands::Schema { file } => {
+            let schema = crate::toml::schema();
+            match file {
+                Some(file) => fs::write(file, serde_json::to_string_pretty(&schema)?)?,
+                // Newline-delimited JSON output keeps the schema on one line
+                None if output::is_json() => println!("{}", serde_json::to_string(&schema)?),
+                None => println!("{}", serde_json::to_string_pretty(&schema)?),
+            }
+        }
+        Commands::Validate { file, url } => {
+            let source = if let Some(source) = get_source(file, url)? {
+                source
+            } else if let Some(source) = profile.source.clone() {
+                source
+            } else {
+                return Err(CliError::NoSourceSpecified.into());
+            };
+            let pack = fetch_pack(source).await?;
+            let files = pack.mods.len()
+                + pack.resourcepacks.len()
+                + pack.shaderpacks.len()
+                + pack.datapacks.len();
+            output::print(
+                Event::Done { files },
+                format_args!(
+                    "{} version {} is valid, with {} entries and {} features.",
+                    pack.name,
+                    pack.version,
+                    files,
+                    pack.features.len()
+                ),
+            );
+        }
+        Commands::Check {
+            side,
+            file,
+            url,
+            content,
+            optional,
+            with,
+            without,
+        } => {
+            let source = if let Some(source) = get_source(file, url)? {
+                source
+            } else if let Some(source) = profile.source.clone() {
+                source
+            } else {
+                return Err(CliError::NoSourceSpecified.into());
+            };
+            let side = side.or(profile.side).unwrap_or(ModSide::Client);
+            let (resourcepacks, shaderpacks, datapacks) = content.resolve(profile);
+
+            let progress_bar = create_spinner("Parsing pack", "Finished parsing pack.");
+            let mut pack = fetch_pack(source).await?;
+            info!("Parsed pack {}, version {}", pack.name, pack.version);
+            progress_bar.finish();
+            // Selections passed to check aren't saved
+            let mut selections = profile.features.clone();
+            update_selections(&mut selections, with, without);
+            let enabled = select_features(&mut pack, &selections);
+            for feature in pack.features.iter() {
+                output::print(
+                    Event::Feature {
+                        name: &feature.name,
+                        enabled: enabled.contains(&feature.name),
+                        description: feature.description.as_deref(),
+                    },
+                    format_args!(
+                        "Feature {} ({}){}",
+                        feature.name,
+                        if enabled.contains(&feature.name) {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        },
+                        feature
+                            .description
+                            .as_ref()
+                            .map(|d| format!(": {d}"))
+                            .unwrap_or_default()
+                    ),
+                );
+            }
+
+            let progress_bar = create_spinner("Fetching mods", "Finished fetching mods.");
+            let prefer = pack.prefer;
+            let on_incompatible = pack.on_incompatible;
+            let mut resolved =
+                download::get_downloadables(side, resourcepacks, shaderpacks, datapacks, pack)
+                    .await?;
+            download::dedupe(&mut resolved, prefer);
+            progress_bar.finish();
+            resolved.iter().for_each(emit_resolved);
+
+            if optional {
+                print_optional(&resolved).await?;
+            }
+            // Incompatibilities are problems for check, even if upgrades only warn about them
+            let incompatible = download::check_incompatibilities(&resolved, on_incompatible)?;
+            if !incompatible.is_empty() {
+                return Err(BreezeError::Incompatible(incompatible.len()).into());
+            }
+            output::print(
+                Event::Done {
+                    files: resolved.len(),
+                },
+                format_args!("Resolved {} files, no problems found.", resolved.len()),
+            );
         }
     };
     Ok(())
 }
 
+/// Shown by `outdated` for files that aren't installed at all
+const NOT_INSTALLED: &str = "not installed";
+
 #[derive(Error, Debug)]
 enum CliError {
     #[error("no file or path was specified")]
     NoSourceSpecified,
-    // #[error("no Minecraft directory was specified")]
-    // NoModDirSpecified,
-    #[error("expected plain text from URL response, got {0}. check the specified URL")]
-    NonPlainTextResponse(String),
+    #[error("no Minecraft directory was specified, pass it with --dir or set it with the config command")]
+    NoModDirSpecified,
+    #[error("world {0} doesn't exist in the saves folder")]
+    WorldNotFound(String),
+    #[error("profile {0} doesn't exist")]
+    ProfileNotFound(String),
+    #[error("profile {0} already exists")]
+    ProfileExists(String),
+    #[error("the default profile can't be removed")]
+    RemoveDefaultProfile,
+    #[error("{0:?} is outside the Minecraft directory")]
+    OutsideMcDir(PathBuf),
+    #[error("{0:?} already exists")]
+    FileExists(PathBuf),
+    #[error("{0} is not a file, URL or git revision and path")]
+    DiffSourceNotFound(String),
+    #[error("couldn't read {0} from git: {1}")]
+    GitShowFailed(String, String),
 }
 
 fn get_source(file: Option<PathBuf>, url: Option<Url>) -> Result<Option<PathOrUrl>> {
@@ -204,7 +1004,260 @@ fn get_source(file: Option<PathBuf>, url: Option<Url>) -> Result<Option<PathOrUr
     }
 }
 
-fn create_spinner(msg: &str, finish: &str) -> ProgressBar {
+/// Print the optional and embedded dependencies of the `resolved` files that aren't resolved themselves
+/// Optional dependencies can be added to the pack with `include_optional` in the `[dependencies]` table
+async fn print_optional(resolved: &[ResolvedFile]) -> Result<()> {
+    let is_resolved = |id: &ModId| resolved.iter().any(|r| &r.id == id);
+    let ids: Vec<ModId> = resolved
+        .iter()
+        .flat_map(|r| r.optional.iter().chain(r.embedded.iter()))
+        .filter(|id| !is_resolved(id))
+        .unique()
+        .cloned()
+        .collect();
+    if ids.is_empty() {
+        output::text("No optional or embedded dependencies.");
+        return Ok(());
+    }
+    let names = download::get_project_fields(&ids, ProjectField::Name).await?;
+    let dependency = |id| Dependency {
+        id,
+        name: names.get(id).cloned().unwrap_or_else(|| id.to_string()),
+    };
+    for r in resolved {
+        let optional: Vec<Dependency> = r
+            .optional
+            .iter()
+            .filter(|id| !is_resolved(id))
+            .map(dependency)
+            .collect();
+        let embedded: Vec<Dependency> = r
+            .embedded
+            .iter()
+            .filter(|id| !is_resolved(id))
+            .map(dependency)
+            .collect();
+        if optional.is_empty() && embedded.is_empty() {
+            continue;
+        }
+        let mut text = format!("{} ({}):", r.name, r.id);
+        for (kind, dependency) in optional
+            .iter()
+            .map(|d| ("optional", d))
+            .chain(embedded.iter().map(|d| ("embedded", d)))
+        {
+            let _ = write!(
+                text,
+                "\n    {}: {} ({})",
+                kind, dependency.name, dependency.id
+            );
+        }
+        output::print(
+            Event::Optional {
+                name: &r.name,
+                id: &r.id,
+                optional,
+                embedded,
+            },
+            text,
+        );
+    }
+    Ok(())
+}
+
+/// Get the directories to install datapacks into, relative to the Minecraft directory
+/// These are the datapack folders of the `worlds` if any were given, otherwise the `global` datapack folder
+fn get_datapack_dirs(
+    mc_dir: &Path,
+    global: Option<PathBuf>,
+    worlds: &[String],
+) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for world in worlds {
+        let dir = Path::new("saves").join(world);
+        if !mc_dir.join(&dir).is_dir() {
+            return Err(CliError::WorldNotFound(world.clone()).into());
+        }
+        dirs.push(dir.join("datapacks"));
+    }
+    if dirs.is_empty() {
+        match global {
+            Some(global) => dirs.push(global),
+            None => warn!("The pack has no global datapack folder and no worlds were selected, datapacks won't be installed"),
+        }
+    }
+    // Files are written to and removed from these directories
+    if let Some(dir) = dirs.iter().find(|dir| !crate::toml::is_contained(dir)) {
+        return Err(CliError::OutsideMcDir(dir.clone()).into());
+    }
+    Ok(dirs)
+}
+
+/// Record the features passed to `--with` and `--without` in the `selections`
+fn update_selections(
+    selections: &mut BTreeMap<String, bool>,
+    with: Vec<String>,
+    without: Vec<String>,
+) {
+    for feature in with {
+        selections.insert(feature, true);
+    }
+    for feature in without {
+        selections.insert(feature, false);
+    }
+}
+
+/// Remove the mods of the features of the `pack` that aren't enabled by the `selections` or by default
+/// Returns the names of the enabled features
+fn select_features(pack: &mut Pack, selections: &BTreeMap<String, bool>) -> Vec<String> {
+    for feature in selections.keys() {
+        if !pack.features.iter().any(|f| &f.name == feature) {
+            warn!("The pack has no feature named {}", feature);
+        }
+    }
+    let enabled = pack.enabled_features(selections);
+    for feature in pack.features.iter() {
+        info!(
+            "Feature {}: {}",
+            feature.name,
+            if enabled.contains(&feature.name) {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+    pack.retain_features(&enabled);
+    enabled
+}
+
+/// Read and parse a version of a pack for `diff`
+/// The `source` is a URL, a file, or a git revision and path separated by `:`
+async fn read_diff_source(source: &str) -> Result<Pack> {
+    if let Some(url) = Url::parse(source)
+        .ok()
+        .filter(|url| ["http", "https"].contains(&url.scheme()))
+    {
+        return fetch_pack(PathOrUrl::Url(url)).await;
+    }
+    if Path::new(source).exists() {
+        return fetch_pack(PathOrUrl::Path(fs::canonicalize(source)?)).await;
+    }
+    let Some((rev, path)) = source.split_once(':') else {
+        return Err(CliError::DiffSourceNotFound(source.to_string()).into());
+    };
+    // Git resolves paths starting with `./` relative to the current directory instead of the repository root
+    let object = if path.starts_with("./") || path.starts_with("../") {
+        source.to_string()
+    } else {
+        format!("{rev}:./{path}")
+    };
+    let output = std::process::Command::new("git")
+        .args(["show", &object])
+        .output()?;
+    if !output.status.success() {
+        return Err(CliError::GitShowFailed(
+            source.to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )
+        .into());
+    }
+    // Packs it extends are read from the working tree
+    let path = fs::canonicalize(path).ok().map(PathOrUrl::Path);
+    crate::toml::parse(String::from_utf8(output.stdout)?, path.as_ref()).await
+}
+
+/// Read the pack TOML from the `source` and parse it
+async fn fetch_pack(source: PathOrUrl) -> Result<Pack> {
+    let toml = crate::toml::read(&source).await?;
+    crate::toml::parse(toml, Some(&source)).await
+}
+
+/// Show the changelogs of the installed files in `mc_dir` that are replaced by the `resolved` files,
+/// and write them to `CHANGELOG-<DATE>.md` in `mc_dir` if `write` is set
+async fn show_changelogs(
+    mc_dir: &Path,
+    resolved: &[ResolvedFile],
+    mc_version: &str,
+    loader: &ModLoader,
+    write: bool,
+) -> Result<()> {
+    let Some(manifest) = manifest::read(mc_dir)? else {
+        warn!("The instance has no install manifest, changelogs can't be shown");
+        return Ok(());
+    };
+    let progress_bar = create_spinner("Fetching changelogs", "Finished fetching changelogs.");
+    let changelogs = changelog::get_changelogs(&manifest, resolved, mc_version, loader).await;
+    progress_bar.finish();
+    // Missing changelogs shouldn't stop the upgrade
+    let changelogs = match changelogs {
+        Ok(ok) => ok,
+        Err(err) => {
+            warn!("Failed to fetch changelogs: {}", err);
+            return Ok(());
+        }
+    };
+    if changelogs.is_empty() {
+        return Ok(());
+    }
+    for changelog in changelogs.iter() {
+        output::print(Event::Changelog(changelog), changelog.to_markdown());
+    }
+    if write {
+        let date = chrono::Local::now().format("%Y-%m-%d");
+        let markdown = changelogs.iter().map(|c| c.to_markdown()).join("\n");
+        let path = mc_dir.join(format!("CHANGELOG-{date}.md"));
+        info!("Writing changelogs to {:?}", path);
+        fs::write(path, format!("# Changelog {date}\n\n{markdown}"))?;
+    }
+    Ok(())
+}
+
+/// Report a resolved file with `--output json`
+fn emit_resolved(resolved: &ResolvedFile) {
+    output::emit(Event::Resolved {
+        name: &resolved.name,
+        id: &resolved.id,
+        file_id: &resolved.file_id,
+        version: &resolved.version,
+        filename: resolved.downloadable.filename(),
+    });
+}
+
+/// A spinner for a stage of a command, reported as progress events with `--output json`
+/// or log messages when not interactive
+struct Spinner {
+    progress_bar: ProgressBar,
+    stage: String,
+    finish: String,
+}
+
+impl Spinner {
+    fn finish(&self) {
+        self.progress_bar.finish();
+        if !output::is_interactive() {
+            info!("{}", self.finish);
+        }
+        output::emit(Event::Progress {
+            stage: &self.stage,
+            finished: true,
+        });
+    }
+}
+
+fn create_spinner(msg: &str, finish: &str) -> Spinner {
+    if !output::is_interactive() {
+        info!("{}...", msg);
+        output::emit(Event::Progress {
+            stage: msg,
+            finished: false,
+        });
+        return Spinner {
+            progress_bar: ProgressBar::hidden(),
+            stage: msg.to_string(),
+            finish: finish.to_string(),
+        };
+    }
     let progress_bar = ProgressBar::new_spinner().with_style(
         ProgressStyle::with_template("{spinner:.green}")
             .unwrap()
@@ -216,7 +1269,11 @@ fn create_spinner(msg: &str, finish: &str) -> ProgressBar {
             ]),
     );
     progress_bar.enable_steady_tick(Duration::from_millis(300));
-    progress_bar
+    Spinner {
+        progress_bar,
+        stage: msg.to_string(),
+        finish: finish.to_string(),
+    }
 }
 
 #[test]
@@ -1,21 +1,109 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::PathBuf};
+use thiserror::Error;
 use url::Url;
 
 use crate::structs::ModSide;
 
+/// The name of the profile stored at the top level of the config
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The version of the config schema, increased whenever existing configs need to be migrated
+pub const CONFIG_VERSION: u64 = 1;
+
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct Config {
+    /// The version of the schema the config was written with
+    #[serde(default)]
+    pub version: u64,
+    /// The profile used when no `--profile` is passed, the default profile if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(flatten)]
+    pub default: Profile,
+}
+
+impl Config {
+    /// Get the profile with the `name`
+    pub fn profile_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        if name == DEFAULT_PROFILE {
+            Some(&mut self.default)
+        } else {
+            self.profiles.get_mut(name)
+        }
+    }
+}
+
+/// Migrate a config `value` written with an older schema to the current one
+pub fn migrate(mut value: Value) -> Result<Value, ConfigError> {
+    let object = value.as_object_mut().ok_or(ConfigError::InvalidFormat)?;
+    let version = match object.get("version") {
+        Some(version) => version.as_u64().ok_or(ConfigError::InvalidFormat)?,
+        None => 0,
+    };
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::NewerVersion(version));
+    }
+    for from in version..CONFIG_VERSION {
+        match from {
+            // Configs without a version have the same fields as version 1
+            0 => {}
+            _ => unreachable!("no migration from config version {from}"),
+        }
+    }
+    object.insert(String::from("version"), CONFIG_VERSION.into());
+    Ok(value)
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("no config directory was found for this system, set MODBREEZE_CONFIG_PATH instead")]
+    NoConfigDir,
+    #[error("the config isn't a valid modbreeze config")]
+    InvalidFormat,
+    #[error("the config was written by a newer version of modbreeze (config version {0})")]
+    NewerVersion(u64),
+}
+
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Profile {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mc_dir: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<PathOrUrl>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub side: Option<ModSide>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resourcepacks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shaderpacks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datapacks: Option<bool>,
+    /// Worlds to install datapacks into
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub worlds: Vec<String>,
+    /// Features of the pack that were enabled or disabled explicitly
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub features: BTreeMap<String, bool>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 pub enum PathOrUrl {
     Path(PathBuf),
     Url(Url),
 }
+
+#[test]
+fn migrate_unversioned_config() {
+    let value = serde_json::json!({ "side": "Server", "features": { "shaders": true } });
+    let config: Config = serde_json::from_value(migrate(value).unwrap()).unwrap();
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert_eq!(config.default.side, Some(ModSide::Server));
+    assert!(matches!(
+        migrate(serde_json::json!({ "version": CONFIG_VERSION + 1 })),
+        Err(ConfigError::NewerVersion(_))
+    ));
+}
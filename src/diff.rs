@@ -0,0 +1,260 @@
+use crate::structs::{Mod, Pack, ResolvedFile};
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+/// A difference between two versions of a pack
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        kind: &'static str,
+        name: String,
+        id: String,
+    },
+    Removed {
+        kind: &'static str,
+        name: String,
+    },
+    /// A setting of the pack or one of its entries changed
+    Changed {
+        subject: String,
+        field: &'static str,
+        from: String,
+        to: String,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { kind, name, id } => write!(f, "+ {kind}: {name} ({id})"),
+            Change::Removed { kind, name } => write!(f, "- {kind}: {name}"),
+            Change::Changed {
+                subject,
+                field,
+                from,
+                to,
+            } => write!(f, "~ {subject}: {field} {from} -> {to}"),
+        }
+    }
+}
+
+impl Change {
+    /// The name of the entry that changed, or `pack` for settings of the pack
+    fn name(&self) -> &str {
+        match self {
+            Change::Added { name, .. } | Change::Removed { name, .. } => name,
+            Change::Changed { subject, .. } => subject,
+        }
+    }
+}
+
+/// Push a change of the `field` of the `subject` to `changes` if `from` and `to` differ
+fn compare(
+    changes: &mut Vec<Change>,
+    subject: &str,
+    field: &'static str,
+    from: String,
+    to: String,
+) {
+    if from != to {
+        changes.push(Change::Changed {
+            subject: subject.to_string(),
+            field,
+            from,
+            to,
+        });
+    }
+}
+
+/// Format an optional setting, `-` if it isn't set
+fn optional(value: &Option<impl Display>) -> String {
+    value
+        .as_ref()
+        .map_or_else(|| String::from("-"), ToString::to_string)
+}
+
+/// Find the differences between the `old` and `new` versions of a pack, including mods of disabled features
+pub fn diff_packs(old: &Pack, new: &Pack) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut pack = |field, from: String, to: String| compare(&mut changes, "pack", field, from, to);
+    pack("name", old.name.clone(), new.name.clone());
+    pack("version", old.version.clone(), new.version.clone());
+    pack(
+        "loader",
+        format!("{:?}", old.loader),
+        format!("{:?}", new.loader),
+    );
+    pack("mc_version", old.mc_version.clone(), new.mc_version.clone());
+    pack(
+        "prefer",
+        format!("{:?}", old.prefer),
+        format!("{:?}", new.prefer),
+    );
+    pack(
+        "on_incompatible",
+        format!("{:?}", old.on_incompatible),
+        format!("{:?}", new.on_incompatible),
+    );
+    pack(
+        "default_shader",
+        optional(&old.default_shader),
+        optional(&new.default_shader),
+    );
+    let kinds = [
+        ("mods", &old.mods, &new.mods),
+        ("resourcepacks", &old.resourcepacks, &new.resourcepacks),
+        ("shaderpacks", &old.shaderpacks, &new.shaderpacks),
+        ("datapacks", &old.datapacks, &new.datapacks),
+    ];
+    for (kind, old_mods, new_mods) in kinds {
+        // The entries come from hash maps, so they're sorted to keep the output stable
+        let start = changes.len();
+        for old_mod in old_mods.iter() {
+            match new_mods.iter().find(|m| m.name == old_mod.name) {
+                Some(new_mod) => diff_mods(&mut changes, old_mod, new_mod),
+                None => changes.push(Change::Removed {
+                    kind,
+                    name: old_mod.name.clone(),
+                }),
+            }
+        }
+        for new_mod in new_mods.iter() {
+            if !old_mods.iter().any(|m| m.name == new_mod.name) {
+                changes.push(Change::Added {
+                    kind,
+                    name: new_mod.name.clone(),
+                    id: new_mod.id.to_string(),
+                });
+            }
+        }
+        changes[start..].sort_by(|a, b| a.name().cmp(b.name()));
+    }
+    changes
+}
+
+fn diff_mods(changes: &mut Vec<Change>, old: &Mod, new: &Mod) {
+    let mut field = |field, from: String, to: String| compare(changes, &old.name, field, from, to);
+    field("id", old.id.to_string(), new.id.to_string());
+    field("side", format!("{:?}", old.side), format!("{:?}", new.side));
+    field(
+        "ignore_loader",
+        old.ignore_loader.to_string(),
+        new.ignore_loader.to_string(),
+    );
+    field(
+        "ignore_version",
+        old.ignore_version.to_string(),
+        new.ignore_version.to_string(),
+    );
+    field(
+        "alternatives",
+        old.alternatives.iter().join(", "),
+        new.alternatives.iter().join(", "),
+    );
+    field("feature", optional(&old.feature), optional(&new.feature));
+    field("order", optional(&old.order), optional(&new.order));
+}
+
+/// Find the differences between the files resolved for the `old` and `new` versions of a pack
+pub fn diff_resolved(old: &[ResolvedFile], new: &[ResolvedFile]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for old_file in old {
+        match new.iter().find(|r| r.id == old_file.id) {
+            Some(new_file) => compare(
+                &mut changes,
+                &old_file.name,
+                "file",
+                old_file.version.clone(),
+                new_file.version.clone(),
+            ),
+            None => changes.push(Change::Removed {
+                kind: "files",
+                name: old_file.name.clone(),
+            }),
+        }
+    }
+    for new_file in new {
+        if !old.iter().any(|r| r.id == new_file.id) {
+            changes.push(Change::Added {
+                kind: "files",
+                name: new_file.name.clone(),
+                id: new_file.id.to_string(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    changes
+}
+
+#[tokio::test]
+async fn diff_pack_versions() {
+    let old = crate::toml::parse(
+        String::from(
+            r#"
+            name = "pack"
+            version = "1.0"
+            loader = "fabric"
+            mc_version = "1.19.2"
+            [mods.client]
+            sodium = "AANobbMI"
+            iris = "YL57xq9U"
+            [mods.common]
+            lithium = "gvQqBUqZ"
+            starlight = "H8CaAYZC"
+            modmenu = "mOgUt4GM"
+            [resourcepacks]
+            faithful = { id = "faithful-32x" }
+            "#,
+        ),
+        None,
+    )
+    .await
+    .unwrap();
+    let mut new = crate::toml::parse(
+        String::from(
+            r#"
+            name = "pack"
+            version = "1.1"
+            loader = "fabric"
+            mc_version = "1.20.1"
+            [mods.client]
+            iris = "YL57xq9U"
+            [mods.common]
+            sodium = "AANobbMI"
+            modmenu = "mOgUt4GM"
+            ferritecore = "uXXizFIs"
+            entityculling = "NNAgCjsB"
+            [resourcepacks]
+            faithful = { id = "faithful-32x", order = 1 }
+            fresh-animations = "50dA9Sha"
+            "#,
+        ),
+        None,
+    )
+    .await
+    .unwrap();
+    let changes: Vec<String> = diff_packs(&old, &new)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            "~ pack: version 1.0 -> 1.1",
+            "~ pack: mc_version 1.19.2 -> 1.20.1",
+            "+ mods: entityculling ([Modrinth]NNAgCjsB)",
+            "+ mods: ferritecore ([Modrinth]uXXizFIs)",
+            "- mods: lithium",
+            "~ sodium: side Client -> All",
+            "- mods: starlight",
+            "~ faithful: order - -> 1",
+            "+ resourcepacks: fresh-animations ([Modrinth]50dA9Sha)",
+        ]
+    );
+    // The order doesn't depend on the order the entries were parsed in
+    let expected = diff_packs(&old, &new);
+    new.mods.reverse();
+    assert_eq!(diff_packs(&old, &new), expected);
+}
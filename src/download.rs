@@ -1,22 +1,33 @@
 use crate::{
     errors::BreezeError,
-    structs::{Mod, ModId, ModSide, Pack},
+    manifest::{Duplicate, Manifest},
+    metadata,
+    output::{self, Event},
+    structs::{ConflictPolicy, DependencyRules, Mod, ModId, ModSide, Pack, Platform, ResolvedFile},
+    toml,
 };
 use anyhow::Result;
 use async_recursion::async_recursion;
-use ferinth::{structures::version::DependencyType, Ferinth};
+use ferinth::{
+    structures::{project::ProjectSupportRange, version::DependencyType},
+    Ferinth,
+};
 use fs_extra::file::{move_file, CopyOptions as FileCopyOptions};
-use furse::{structures::file_structs::FileRelationType, Furse};
+use furse::{
+    structures::file_structs::{FileRelationType, HashAlgo},
+    Furse,
+};
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use libium::{
     config::structs::ModLoader,
     upgrade::{mod_downloadable, Downloadable},
 };
-use log::{error, info};
+use log::{error, info, warn};
 use rayon::prelude::*;
 use reqwest::Client;
 use std::{
+    collections::{HashMap, HashSet},
     fs::read_dir,
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
@@ -29,14 +40,8 @@ use tokio::{
     task::JoinSet,
 };
 
-/// Get the `Downloadable`s for the mods in a `Pack`
-/// Returns a `Vec` of the `Downloadable`s
-pub async fn get_downloadables(
-    side: ModSide,
-    resourcepacks: bool,
-    shaderpacks: bool,
-    pack: Pack,
-) -> Result<Vec<Downloadable>> {
+/// Create the CurseForge and Modrinth API clients
+pub fn api_clients() -> Result<(Furse, Ferinth)> {
     let api_key = env!("CF_API_KEY");
     let furse = Furse::new(api_key);
     let ferinth = Ferinth::new(
@@ -45,11 +50,220 @@ pub async fn get_downloadables(
         Some("Mr. Icecream#9624"),
         None,
     )?;
+    Ok((furse, ferinth))
+}
+
+/// Whether resolving failed because the project has no file that can be used
+fn is_unavailable(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<BreezeError>(),
+        Some(BreezeError::NoCompatFile(..) | BreezeError::DistributionDenied(..))
+    )
+}
+
+/// A field of a project on either platform
+#[derive(Clone, Copy)]
+pub enum ProjectField {
+    /// The display name
+    Name,
+    /// The name used in URLs
+    Slug,
+    /// The web page
+    Url,
+}
+
+/// Get the `field` of the projects in `ids`
+/// Projects that couldn't be found are missing from the returned map
+pub async fn get_project_fields(
+    ids: &[ModId],
+    field: ProjectField,
+) -> Result<HashMap<ModId, String>> {
+    let (furse, ferinth) = api_clients()?;
+    let mut fields = HashMap::new();
+    let curseforge_ids: Vec<i32> = ids
+        .iter()
+        .filter_map(|id| match id {
+            ModId::CurseForgeId(id) => (*id).try_into().ok(),
+            ModId::ModrinthId(_) => None,
+        })
+        .collect();
+    if !curseforge_ids.is_empty() {
+        for project in furse.get_mods(curseforge_ids).await? {
+            let value = match field {
+                ProjectField::Name => project.name,
+                ProjectField::Slug => project.slug,
+                ProjectField::Url => project.links.website_url.to_string(),
+            };
+            fields.insert(ModId::CurseForgeId(project.id as u32), value);
+        }
+    }
+    let modrinth_ids: Vec<&str> = ids
+        .iter()
+        .filter_map(|id| match id {
+            ModId::ModrinthId(id) => Some(id.as_str()),
+            ModId::CurseForgeId(_) => None,
+        })
+        .collect();
+    if !modrinth_ids.is_empty() {
+        for project in ferinth.get_multiple_projects(&modrinth_ids).await? {
+            let value = match field {
+                ProjectField::Name => project.title,
+                ProjectField::Slug => project.slug,
+                ProjectField::Url => format!("https://modrinth.com/project/{}", project.slug),
+            };
+            fields.insert(ModId::ModrinthId(project.id), value);
+        }
+    }
+    Ok(fields)
+}
+
+/// Replace the Modrinth slugs in the dependency `rules`, including replacement projects, with project IDs,
+/// since dependencies are always referred to by their project IDs
+async fn resolve_rule_slugs(ferinth: &Ferinth, rules: &mut DependencyRules) -> Result<()> {
+    let ids: Vec<&str> = rules
+        .exclude
+        .iter()
+        .chain(
+            rules
+                .replace
+                .iter()
+                .flat_map(|(id, replacement)| [id, replacement]),
+        )
+        .chain(rules.include_optional.iter())
+        .filter_map(|id| match id {
+            ModId::ModrinthId(id) => Some(id.as_str()),
+            ModId::CurseForgeId(_) => None,
+        })
+        .unique()
+        .collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let projects = ferinth.get_multiple_projects(&ids).await?;
+    let resolve = |id: &mut ModId| {
+        if let ModId::ModrinthId(slug) = id {
+            match projects.iter().find(|p| &p.id == slug || &p.slug == slug) {
+                Some(project) => *slug = project.id.clone(),
+                None => warn!(
+                    "The dependency rule for {} doesn't match any Modrinth project",
+                    slug
+                ),
+            }
+        }
+    };
+    rules.exclude.iter_mut().for_each(resolve);
+    rules.replace.iter_mut().for_each(|(id, replacement)| {
+        resolve(id);
+        resolve(replacement);
+    });
+    rules.include_optional.iter_mut().for_each(resolve);
+    Ok(())
+}
+
+/// The side a project runs on, judging by its `client` and `server` support
+fn supported_side(client: &ProjectSupportRange, server: &ProjectSupportRange) -> ModSide {
+    match (client, server) {
+        (ProjectSupportRange::Unsupported, ProjectSupportRange::Unsupported) => ModSide::All,
+        (_, ProjectSupportRange::Unsupported) => ModSide::Client,
+        (ProjectSupportRange::Unsupported, _) => ModSide::Server,
+        _ => ModSide::All,
+    }
+}
+
+/// Detect the side of the `mods` from the client and server support of their Modrinth projects
+/// Mods in `[mods.auto]` and dependencies get the detected side,
+/// other mods are warned about if they're in the wrong category
+async fn detect_sides(ferinth: &Ferinth, mods: &mut [Mod]) -> Result<()> {
+    let is_mod = |mod_: &Mod| {
+        matches!(
+            mod_.side,
+            ModSide::Client | ModSide::Server | ModSide::All | ModSide::Auto
+        )
+    };
+    let ids: Vec<&str> = mods
+        .iter()
+        .filter(|mod_| is_mod(mod_))
+        .filter_map(|mod_| match &mod_.id {
+            ModId::ModrinthId(id) => Some(id.as_str()),
+            ModId::CurseForgeId(_) => None,
+        })
+        .unique()
+        .collect();
+    let projects = if ids.is_empty() {
+        Vec::new()
+    } else {
+        match ferinth.get_multiple_projects(&ids).await {
+            Ok(projects) => projects,
+            // Without `[mods.auto]` the sides are only used for warnings and dependencies
+            Err(err) if !mods.iter().any(|mod_| mod_.side == ModSide::Auto) => {
+                warn!("Failed to detect the sides of mods: {}", err);
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    for mod_ in mods.iter_mut().filter(|mod_| is_mod(mod_)) {
+        let project = match &mod_.id {
+            ModId::ModrinthId(id) => projects.iter().find(|p| &p.id == id || &p.slug == id),
+            ModId::CurseForgeId(_) => None,
+        };
+        let Some(project) = project else {
+            if mod_.side == ModSide::Auto {
+                warn!(
+                    "The side of {} can't be detected, installing it on both sides",
+                    mod_.name
+                );
+                mod_.side = ModSide::All;
+            }
+            continue;
+        };
+        let detected = supported_side(&project.client_side, &project.server_side);
+        if mod_.side == ModSide::Auto || !mod_.required_by.is_empty() {
+            mod_.side = detected;
+        } else if detected != ModSide::All && mod_.side != detected {
+            warn!(
+                "{} is listed for {}, but it only runs on the {}",
+                mod_.name,
+                if mod_.side == ModSide::All {
+                    "both sides"
+                } else {
+                    "the other side"
+                },
+                if detected == ModSide::Client {
+                    "client"
+                } else {
+                    "server"
+                }
+            );
+        } else if mod_.side != ModSide::All
+            && project.client_side == ProjectSupportRange::Required
+            && project.server_side == ProjectSupportRange::Required
+        {
+            warn!(
+                "{} is only listed for one side, but it is required on both sides",
+                mod_.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Get the files to download for the mods in a `Pack`
+/// Returns a `Vec` of the `ResolvedFile`s
+pub async fn get_downloadables(
+    side: ModSide,
+    resourcepacks: bool,
+    shaderpacks: bool,
+    datapacks: bool,
+    pack: Pack,
+) -> Result<Vec<ResolvedFile>> {
+    let (furse, ferinth) = api_clients()?;
+    let mut mods = pack.mods;
+    detect_sides(&ferinth, &mut mods).await?;
     let mods = if side == ModSide::All {
-        pack.mods
+        mods
     } else {
-        pack.mods
-            .into_par_iter()
+        mods.into_par_iter()
             .filter(|mod_| mod_.side == side || mod_.side == ModSide::All)
             .collect()
     };
@@ -57,11 +271,8 @@ pub async fn get_downloadables(
     #[async_recursion(?Send)]
     async fn inner(
         mods: Vec<Mod>,
-        furse: &Furse,
-        ferinth: &Ferinth,
-        mc_version: String,
-        loader: ModLoader,
-        to_download: Arc<RwLock<Vec<Downloadable>>>,
+        resolver: Arc<Resolver>,
+        to_download: Arc<RwLock<Vec<ResolvedFile>>>,
         output: Arc<String>,
     ) -> Result<()> {
         let dependencies: Arc<Mutex<Vec<Mod>>> = Arc::new(Mutex::new(Vec::new()));
@@ -69,175 +280,98 @@ pub async fn get_downloadables(
         let semaphore = Arc::new(Semaphore::new(75));
         for mod_ in mods.iter() {
             let permit = semaphore.clone().acquire_owned().await?;
-            let furse = furse.clone();
-            let ferinth = ferinth.clone();
-            let mc_version = mc_version.clone();
-            let loader = loader.clone();
+            let resolver = resolver.clone();
             let to_download = to_download.clone();
             let output = output.clone();
             let mod_ = mod_.clone();
             let dependencies = dependencies.clone();
             tasks.spawn(async move {
-                let mc_version_to_check = if mod_.ignore_version {
-                    None
-                } else {
-                    Some(mc_version)
-                };
-                let loader_to_check = if mod_.ignore_loader {
-                    None
-                } else {
-                    Some(loader)
-                };
                 let _permit = permit;
-                let downloadable = match mod_.id.clone() {
-                    ModId::CurseForgeId(id) => match mod_downloadable::get_latest_compatible_file(
-                        furse.get_mod_files(id.try_into()?).await?,
-                        mc_version_to_check.as_deref(),
-                        loader_to_check.as_ref(),
-                    ) {
-                        None => Err(BreezeError::NoCompatFile(
-                            mod_.name.clone(),
-                            mod_.id.clone(),
-                        )),
-                        Some(ok) => {
-                            info!("Got file for mod {}, id: {}", mod_.name, mod_.id);
-                            let dependencies_: Vec<Mod> = ok
-                                .0
-                                .dependencies
-                                .into_iter()
-                                .filter(|d| d.relation_type == FileRelationType::RequiredDependency)
-                                .map(|d| Mod {
-                                    name: format!("Dependency of {}", &mod_.name),
-                                    id: ModId::CurseForgeId(d.mod_id as u32),
-                                    ignore_loader: mod_.ignore_loader,
-                                    ignore_version: mod_.ignore_version,
-                                    side: mod_.side, // doesn't matter in this situation
-                                })
-                                .collect();
-                            dependencies_.into_iter().for_each(|d| {
-                                let mut dependencies = dependencies.lock().expect("Mutex poisoned");
-                                if !dependencies.contains(&d) {
-                                    info!("Adding dependency: {}, id: {}", d.name, d.id);
-                                    dependencies.push(d);
-                                }
+                let mut res = get_downloadable(&resolver, &mod_, &mod_.id, &output).await;
+                // Try the alternatives in order until one of them resolves
+                // Other errors, e.g. from the network, shouldn't silently swap in another project
+                for alternative in mod_.alternatives.iter() {
+                    let err = match &res {
+                        Err(err) if is_unavailable(err) => err,
+                        _ => break,
+                    };
+                    warn!(
+                        "{}, trying alternative for mod {}, id: {}",
+                        err, mod_.name, alternative
+                    );
+                    res = get_downloadable(&resolver, &mod_, alternative, &output).await;
+                    if res.is_ok() {
+                        warn!("Using {} for mod {}", alternative, mod_.name);
+                    }
+                }
+                match res {
+                    Ok((resolved, dependencies_)) => {
+                        to_download.write().await.push(resolved);
+                        dependencies
+                            .lock()
+                            .expect("Mutex poisoned")
+                            .extend(dependencies_);
+                    }
+                    Err(err) => match err.downcast_ref::<BreezeError>() {
+                        Some(err) => {
+                            error!("{}", err);
+                            output::emit(Event::Failed {
+                                name: &mod_.name,
+                                error: err.to_string(),
                             });
-
-                            Ok(Downloadable {
-                                download_url: ok.0.download_url.ok_or(
-                                    BreezeError::DistributionDenied(
-                                        mod_.name.clone(),
-                                        mod_.id.clone(),
-                                    ),
-                                )?,
-                                output: PathBuf::from(if ok.0.file_name.ends_with(".jar") {
-                                    "mods"
-                                } else {
-                                    &output
-                                })
-                                .join(ok.0.file_name),
-                                length: ok.0.file_length as u64,
-                            })
-                        }
-                    },
-                    ModId::ModrinthId(id) => match mod_downloadable::get_latest_compatible_version(
-                        &ferinth.list_versions(&id.to_string()).await?,
-                        mc_version_to_check.as_deref(),
-                        loader_to_check.as_ref(),
-                    ) {
-                        None => Err(BreezeError::NoCompatFile(
-                            mod_.name.clone(),
-                            mod_.id.clone(),
-                        )),
-                        Some(ok) => {
-                            info!("Got version file for mod {}, id: {}", mod_.name, mod_.id);
-                            for d in ok.1.dependencies.into_iter().filter(|d| {
-                                d.dependency_type == DependencyType::Required
-                                    && (d.project_id.is_some() || d.version_id.is_some())
-                            }) {
-                                let project_id = match d.project_id {
-                                    Some(project_id) => project_id,
-                                    None => {
-                                        if let Ok(ok) =
-                                            ferinth.get_version(&d.version_id.unwrap()).await
-                                        {
-                                            ok.project_id
-                                        } else {
-                                            continue;
-                                        }
-                                    }
-                                };
-                                let d = Mod {
-                                    name: format!("Dependency of {}", &mod_.name),
-                                    id: ModId::ModrinthId(project_id),
-                                    ignore_loader: mod_.ignore_loader,
-                                    ignore_version: mod_.ignore_version,
-                                    side: mod_.side, // doesn't matter in this situation
-                                };
-                                let mut dependencies = dependencies.lock().expect("Mutex poisoned");
-                                if !dependencies.contains(&d) {
-                                    info!("Adding dependency: {}, id: {}", d.name, d.id);
-                                    dependencies.push(d);
-                                }
-                            }
-
-                            Ok(Downloadable {
-                                download_url: ok.0.url,
-                                output: PathBuf::from(if ok.0.filename.ends_with(".jar") {
-                                    "mods"
-                                } else {
-                                    &output
-                                })
-                                .join(ok.0.filename),
-                                length: ok.0.size as u64,
-                            })
                         }
+                        None => return Err(err),
                     },
-                };
-                match downloadable {
-                    Ok(ok) => to_download.write().await.push(ok),
-                    Err(err) => error!("{}", err),
                 }
                 Ok::<(), anyhow::Error>(())
             });
         }
         while let Some(res) = tasks.join_next().await {
-            let _res = res??;
+            res??;
         }
-        let dependencies = dependencies.lock().expect("Mutex poisoned");
-        if !(dependencies.is_empty()) {
-            inner(
-                dependencies.clone(),
-                furse,
-                ferinth,
-                mc_version,
-                loader,
-                to_download,
-                output,
+        let mut dependencies: Vec<Mod> = dependencies
+            .lock()
+            .expect("Mutex poisoned")
+            .drain(..)
+            .filter_map(|d| resolver.rules.apply(d))
+            .collect();
+        detect_sides(&resolver.ferinth, &mut dependencies).await?;
+        // Dependencies only needed on the other side aren't installed
+        dependencies.retain(|d| {
+            !matches!(
+                (resolver.side, d.side),
+                (ModSide::Client, ModSide::Server) | (ModSide::Server, ModSide::Client)
             )
-            .await?;
+        });
+        let dependencies = next_level(&resolver, dependencies, &mut *to_download.write().await)?;
+        if !(dependencies.is_empty()) {
+            inner(dependencies, resolver, to_download, output).await?;
         }
         Ok(())
     }
     let to_download = Arc::new(RwLock::new(Vec::new()));
     let mut futures = Vec::new();
-    let mc_version = pack.mc_version;
-    let loader = pack.loader;
+    let mut rules = pack.dependencies;
+    resolve_rule_slugs(&ferinth, &mut rules).await?;
+    let resolver = Arc::new(Resolver {
+        furse,
+        ferinth,
+        side,
+        mc_version: pack.mc_version,
+        loader: pack.loader,
+        rules,
+        pins: Mutex::new(HashMap::new()),
+    });
     futures.push(inner(
         mods,
-        &furse,
-        &ferinth,
-        mc_version.clone(),
-        loader.clone(),
+        resolver.clone(),
         to_download.clone(),
         Arc::new(String::from("mods")),
     ));
     if resourcepacks {
         futures.push(inner(
             pack.resourcepacks,
-            &furse,
-            &ferinth,
-            mc_version.clone(),
-            loader.clone(),
+            resolver.clone(),
             to_download.clone(),
             Arc::new(String::from("resourcepacks")),
         ));
@@ -245,14 +379,19 @@ pub async fn get_downloadables(
     if shaderpacks {
         futures.push(inner(
             pack.shaderpacks,
-            &furse,
-            &ferinth,
-            mc_version.clone(),
-            loader.clone(),
+            resolver.clone(),
             to_download.clone(),
             Arc::new(String::from("shaderpacks")),
         ));
     }
+    if datapacks {
+        futures.push(inner(
+            pack.datapacks,
+            resolver.clone(),
+            to_download.clone(),
+            Arc::new(String::from("datapacks")),
+        ));
+    }
     for res in futures::future::join_all(futures).await {
         res?
     }
@@ -261,10 +400,293 @@ pub async fn get_downloadables(
         .into_inner())
 }
 
-pub async fn download(output_dir: Arc<PathBuf>, to_download: Vec<Downloadable>) -> Result<()> {
+/// The API clients and pack settings used to resolve files
+struct Resolver {
+    furse: Furse,
+    ferinth: Ferinth,
+    /// The side that is being installed
+    side: ModSide,
+    mc_version: String,
+    loader: ModLoader,
+    rules: DependencyRules,
+    /// The versions of each project required by dependents
+    pins: Mutex<HashMap<ModId, Vec<Pin>>>,
+}
+
+/// A version ID required by a dependent, along with the chain of mods that required it
+type Pin = (String, Vec<String>);
+
+/// Prepare the `dependencies` found in a level of the resolution for the next level
+/// Dependencies that are already in `to_download` are skipped,
+/// unless a dependent requires a different version, in which case the resolved file is replaced
+/// and the replacement keeps the name of the entry, e.g. the name of the mod in the pack
+/// Returns an error if dependents require different versions of the same project
+/// The dependency rules and side filter are applied to the `dependencies` before this
+fn next_level(
+    resolver: &Resolver,
+    dependencies: Vec<Mod>,
+    to_download: &mut Vec<ResolvedFile>,
+) -> Result<Vec<Mod>> {
+    let mut pins = resolver.pins.lock().expect("Mutex poisoned");
+    let mut next: Vec<Mod> = Vec::new();
+    for mut d in dependencies {
+        if let Some(version_id) = &d.pin {
+            let project_pins = pins.entry(d.id.clone()).or_default();
+            project_pins.push((version_id.clone(), d.required_by.clone()));
+            if project_pins.iter().any(|(other, _)| other != version_id) {
+                return Err(BreezeError::Unsatisfiable(
+                    d.id.clone(),
+                    project_pins
+                        .iter()
+                        .map(|(version_id, chain)| {
+                            format!("{} requires version {}", chain.join(" -> "), version_id)
+                        })
+                        .join(", "),
+                )
+                .into());
+            }
+        }
+        if let Some(index) = to_download.iter().position(|r| r.id == d.id) {
+            match &d.pin {
+                Some(version_id) if &to_download[index].file_id != version_id => {
+                    info!(
+                        "{} requires version {} of {}, replacing {}",
+                        d.required_by.join(" -> "),
+                        version_id,
+                        d.id,
+                        to_download[index].version
+                    );
+                    d.name = to_download.swap_remove(index).name;
+                }
+                _ => continue,
+            }
+        }
+        match next.iter_mut().find(|n| n.id == d.id) {
+            // Pinned dependencies win over unpinned ones of the same project
+            Some(n) => {
+                if n.pin.is_none() {
+                    *n = d;
+                }
+            }
+            None => {
+                info!("Adding dependency: {}, id: {}", d.name, d.id);
+                next.push(d);
+            }
+        }
+    }
+    Ok(next)
+}
+
+/// Get the latest compatible file for `mod_` from the project `id`
+/// Returns the `ResolvedFile` and the dependencies of the file to resolve next,
+/// which are the required ones and the optional ones the pack includes
+/// The other relations of the file are recorded in the `ResolvedFile`
+async fn get_downloadable(
+    resolver: &Resolver,
+    mod_: &Mod,
+    id: &ModId,
+    output: &str,
+) -> Result<(ResolvedFile, Vec<Mod>)> {
+    let Resolver {
+        furse,
+        ferinth,
+        mc_version,
+        loader,
+        rules,
+        ..
+    } = resolver;
+    let mc_version_to_check = if mod_.ignore_version {
+        None
+    } else {
+        Some(mc_version.as_str())
+    };
+    let loader_to_check = if mod_.ignore_loader {
+        None
+    } else {
+        Some(loader)
+    };
+    let mut required = Vec::new();
+    let mut optional = Vec::new();
+    let mut embedded = Vec::new();
+    let mut incompatible = Vec::new();
+    let resolved = match id {
+        ModId::CurseForgeId(project_id) => match mod_downloadable::get_latest_compatible_file(
+            furse.get_mod_files((*project_id).try_into()?).await?,
+            mc_version_to_check,
+            loader_to_check,
+        ) {
+            None => return Err(BreezeError::NoCompatFile(mod_.name.clone(), id.clone()).into()),
+            Some(ok) => {
+                info!("Got file for mod {}, id: {}", mod_.name, id);
+                let download_url = ok.0.download_url.ok_or(BreezeError::DistributionDenied(
+                    mod_.name.clone(),
+                    id.clone(),
+                ))?;
+                for d in ok.0.dependencies {
+                    let dependency_id = ModId::CurseForgeId(d.mod_id as u32);
+                    match d.relation_type {
+                        FileRelationType::RequiredDependency => {
+                            required.push((dependency_id, None))
+                        }
+                        FileRelationType::OptionalDependency => optional.push(dependency_id),
+                        FileRelationType::EmbeddedLibrary | FileRelationType::Include => {
+                            embedded.push(dependency_id)
+                        }
+                        FileRelationType::Incompatible => incompatible.push(dependency_id),
+                        FileRelationType::Tool => (),
+                    }
+                }
+
+                ResolvedFile {
+                    name: mod_.name.clone(),
+                    id: id.clone(),
+                    file_id: ok.0.id.to_string(),
+                    version: ok.0.display_name,
+                    sha1: ok
+                        .0
+                        .hashes
+                        .into_iter()
+                        .find(|h| h.algo == HashAlgo::Sha1)
+                        .map(|h| h.value),
+                    optional: Vec::new(),
+                    embedded: Vec::new(),
+                    incompatible: Vec::new(),
+                    downloadable: Downloadable {
+                        download_url,
+                        output: PathBuf::from(if ok.0.file_name.ends_with(".jar") {
+                            "mods"
+                        } else {
+                            output
+                        })
+                        .join(ok.0.file_name),
+                        length: ok.0.file_length as u64,
+                    },
+                }
+            }
+        },
+        ModId::ModrinthId(project_id) => match match &mod_.pin {
+            Some(version_id) => {
+                let version = ferinth.get_version(version_id).await?;
+                version
+                    .files
+                    .iter()
+                    .find(|f| f.primary)
+                    .or_else(|| version.files.first())
+                    .cloned()
+                    .map(|file| (file, version))
+            }
+            None => {
+                let mut versions = ferinth.list_versions(project_id).await?;
+                if mod_.side == ModSide::Datapack {
+                    // Datapack projects can also have versions packaged as mods
+                    versions.retain(|v| v.loaders.iter().any(|l| l == "datapack"));
+                }
+                mod_downloadable::get_latest_compatible_version(
+                    &versions,
+                    mc_version_to_check,
+                    loader_to_check,
+                )
+                .map(|ok| (ok.0, ok.1))
+            }
+        } {
+            None => return Err(BreezeError::NoCompatFile(mod_.name.clone(), id.clone()).into()),
+            Some(ok) => {
+                info!("Got version file for mod {}, id: {}", mod_.name, id);
+                for d in
+                    ok.1.dependencies
+                        .into_iter()
+                        .filter(|d| d.project_id.is_some() || d.version_id.is_some())
+                {
+                    let project_id = match d.project_id {
+                        Some(project_id) => project_id,
+                        None => {
+                            if let Ok(ok) =
+                                ferinth.get_version(d.version_id.as_ref().unwrap()).await
+                            {
+                                ok.project_id
+                            } else {
+                                continue;
+                            }
+                        }
+                    };
+                    let dependency_id = ModId::ModrinthId(project_id);
+                    match d.dependency_type {
+                        DependencyType::Required => required.push((dependency_id, d.version_id)),
+                        DependencyType::Optional => optional.push(dependency_id),
+                        DependencyType::Embedded => embedded.push(dependency_id),
+                        DependencyType::Incompatible => incompatible.push(dependency_id),
+                    }
+                }
+
+                ResolvedFile {
+                    name: mod_.name.clone(),
+                    id: ModId::ModrinthId(ok.1.project_id),
+                    file_id: ok.1.id,
+                    version: ok.1.version_number,
+                    sha1: Some(ok.0.hashes.sha1),
+                    optional: Vec::new(),
+                    embedded: Vec::new(),
+                    incompatible: Vec::new(),
+                    downloadable: Downloadable {
+                        download_url: ok.0.url,
+                        output: PathBuf::from(if ok.0.filename.ends_with(".jar") {
+                            "mods"
+                        } else {
+                            output
+                        })
+                        .join(ok.0.filename),
+                        length: ok.0.size as u64,
+                    },
+                }
+            }
+        },
+    };
+
+    let mut required_by = mod_.required_by.clone();
+    required_by.push(mod_.label());
+    let dependencies = required
+        .into_iter()
+        .chain(
+            optional
+                .iter()
+                .filter(|id| rules.include_optional.contains(id))
+                .map(|id| (id.clone(), None)),
+        )
+        .map(|(id, pin)| Mod {
+            name: format!("Dependency of {}", &mod_.name),
+            id,
+            ignore_loader: mod_.ignore_loader,
+            ignore_version: mod_.ignore_version,
+            // Replaced by the side of the Modrinth project in `detect_sides`, if it has one
+            side: mod_.side,
+            alternatives: Vec::new(),
+            pin,
+            required_by: required_by.clone(),
+            feature: mod_.feature.clone(),
+            order: None,
+        })
+        .collect();
+    Ok((
+        ResolvedFile {
+            optional,
+            embedded,
+            incompatible,
+            ..resolved
+        },
+        dependencies,
+    ))
+}
+
+pub async fn download(output_dir: Arc<PathBuf>, to_download: Vec<ResolvedFile>) -> Result<()> {
     let mut tasks = Vec::new();
     let semaphore = Arc::new(Semaphore::new(75));
-    let progress_bar = ProgressBar::new(count_bytes(&to_download)).with_style(
+    let progress_bar = if !output::is_interactive() {
+        info!("Downloading {} files", to_download.len());
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(count_bytes(&to_download))
+    }
+    .with_style(
         ProgressStyle::with_template(
             "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({bytes}/{total_bytes}) ({percent}%)",
         )?
@@ -272,8 +694,12 @@ pub async fn download(output_dir: Arc<PathBuf>, to_download: Vec<Downloadable>)
         .tick_strings(&["Downloading.  ", "Downloading.. ", "Downloading...", "Finished."])
     );
     progress_bar.enable_steady_tick(Duration::from_millis(300));
+    output::emit(Event::Progress {
+        stage: "Downloading",
+        finished: false,
+    });
     let client = Arc::new(Client::new());
-    for downloadable in to_download {
+    for ResolvedFile { downloadable, .. } in to_download {
         let permit = semaphore.clone().acquire_owned().await?;
         let output_dir = output_dir.clone();
         let progress_bar = progress_bar.clone();
@@ -284,6 +710,7 @@ pub async fn download(output_dir: Arc<PathBuf>, to_download: Vec<Downloadable>)
         }
         tasks.push(spawn(async move {
             let _permit = permit;
+            let path = output_dir.join(&downloadable.output);
             downloadable
                 .download(
                     &client,
@@ -291,6 +718,8 @@ pub async fn download(output_dir: Arc<PathBuf>, to_download: Vec<Downloadable>)
                     |addition| progress_bar.inc(addition.try_into().unwrap()), // increase progress on download update
                 )
                 .await?;
+            info!("Downloaded {:?}", path);
+            output::emit(Event::Added { path: &path });
             Ok::<(), anyhow::Error>(())
         }));
     }
@@ -298,28 +727,255 @@ pub async fn download(output_dir: Arc<PathBuf>, to_download: Vec<Downloadable>)
         handle.await??;
     }
     progress_bar.finish();
+    output::emit(Event::Progress {
+        stage: "Downloading",
+        finished: true,
+    });
     Ok(())
 }
 
-/// Count the total size in bytes of the downloadables
-fn count_bytes(downloadables: &[Downloadable]) -> u64 {
+/// Count the total size in bytes of the resolved files
+fn count_bytes(resolved: &[ResolvedFile]) -> u64 {
     let mut total = 0_u64;
-    for downloadable in downloadables {
-        total += downloadable.length;
+    for resolved in resolved {
+        total += resolved.downloadable.length;
     }
     total
 }
 
+/// Install the resolved datapacks in `to_download` into each of the `directories` relative to the Minecraft directory
+pub fn place_datapacks(to_download: &mut Vec<ResolvedFile>, directories: &[PathBuf]) {
+    let (datapacks, others): (Vec<ResolvedFile>, Vec<ResolvedFile>) = to_download
+        .drain(..)
+        .partition(|resolved| resolved.downloadable.output.starts_with("datapacks"));
+    *to_download = others;
+    for datapack in datapacks {
+        for directory in directories {
+            let mut datapack = datapack.clone();
+            datapack.downloadable.output = directory.join(datapack.downloadable.filename());
+            to_download.push(datapack);
+        }
+    }
+}
+
+/// Remove files in `to_download` that are the same file on different platforms, judging by their hashes
+/// Files from the `prefer`red platform are kept
+pub fn dedupe(to_download: &mut Vec<ResolvedFile>, prefer: Platform) {
+    // Stable sort so the preferred platform comes first without changing the order otherwise
+    to_download.sort_by_key(|resolved| resolved.id.platform() != prefer);
+    let mut seen = HashSet::new();
+    to_download.retain(|resolved| match &resolved.sha1 {
+        Some(sha1) if !seen.insert(sha1.clone()) => {
+            info!(
+                "Skipping duplicate file {} of mod {}, id: {}",
+                resolved.downloadable.filename(),
+                resolved.name,
+                resolved.id
+            );
+            false
+        }
+        _ => true,
+    });
+}
+
+/// Find pairs of files in `resolved` where one declares the other's project incompatible
+pub fn find_incompatibilities(resolved: &[ResolvedFile]) -> Vec<(&ResolvedFile, &ResolvedFile)> {
+    let mut pairs: Vec<(&ResolvedFile, &ResolvedFile)> = Vec::new();
+    for a in resolved {
+        for b in resolved.iter().filter(|b| a.incompatible.contains(&b.id)) {
+            // Skip the pair if it was already found from the other side
+            if !pairs.iter().any(|(x, y)| x.id == b.id && y.id == a.id) {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+/// Report incompatible files in `resolved`
+/// Returns an error if any were found and the `policy` is to fail, otherwise the incompatible pairs
+pub fn check_incompatibilities(
+    resolved: &[ResolvedFile],
+    policy: ConflictPolicy,
+) -> Result<Vec<(&ResolvedFile, &ResolvedFile)>> {
+    let pairs = find_incompatibilities(resolved);
+    for (a, b) in pairs.iter() {
+        let msg = format!(
+            "{} ({}, {}) is incompatible with {} ({}, {})",
+            a.name,
+            a.id,
+            a.downloadable.filename(),
+            b.name,
+            b.id,
+            b.downloadable.filename()
+        );
+        match policy {
+            ConflictPolicy::Fail => error!("{}", msg),
+            ConflictPolicy::Warn => warn!("{}", msg),
+        }
+        output::emit(Event::Incompatible {
+            name: &a.name,
+            id: &a.id,
+            with: &b.name,
+            with_id: &b.id,
+        });
+    }
+    if policy == ConflictPolicy::Fail && !pairs.is_empty() {
+        return Err(BreezeError::Incompatible(pairs.len()).into());
+    }
+    Ok(pairs)
+}
+
+/// Remove the files that were found to be `duplicates` of another file in `resolved` by an earlier upgrade,
+/// so they aren't downloaded again
+/// Returns the duplicates that still apply, because the file kept instead is still resolved
+pub fn skip_duplicates(
+    resolved: &mut Vec<ResolvedFile>,
+    duplicates: &[Duplicate],
+) -> Vec<Duplicate> {
+    let applying: Vec<Duplicate> = duplicates
+        .iter()
+        .filter(|duplicate| resolved.iter().any(|r| r.file_id == duplicate.kept))
+        .cloned()
+        .collect();
+    resolved.retain(|r| {
+        let skip = applying.iter().any(|d| d.file_id == r.file_id);
+        if skip {
+            info!(
+                "Skipping {} of mod {}, it contains the same mod as another file",
+                r.downloadable.filename(),
+                r.name
+            );
+        }
+        !skip
+    });
+    applying
+}
+
+/// Find jars in `resolved` that contain the same mod as another jar, judging by the mod IDs in their metadata
+/// Jars from the `prefer`red platform are kept, the others are removed from `resolved` and moved to `.old` in the `directory`
+/// Returns the removed jars, so later upgrades can skip them before downloading
+pub async fn dedupe_jars(
+    directory: &Path,
+    resolved: &mut Vec<ResolvedFile>,
+    prefer: Platform,
+) -> Result<Vec<Duplicate>> {
+    let mut seen: HashMap<String, &ResolvedFile> = HashMap::new();
+    let mut duplicates = Vec::new();
+    let mut jars: Vec<&ResolvedFile> = resolved
+        .iter()
+        .filter(|r| r.downloadable.filename().ends_with(".jar"))
+        .collect();
+    jars.sort_by_key(|resolved| resolved.id.platform() != prefer);
+    for jar in jars {
+        let path = directory.join(jar.downloadable.filename());
+        let ids = match metadata::read(&path).map(|metadata| metadata.ids) {
+            Ok(ok) => ok,
+            Err(err) => {
+                warn!("Failed to read metadata of {:?}: {}", path, err);
+                continue;
+            }
+        };
+        if let Some(kept) = ids.iter().find_map(|id| seen.get(id)) {
+            warn!(
+                "{} ({}) contains the same mod as {} ({}), keeping the file from {}",
+                jar.name, jar.id, kept.name, kept.id, kept.id
+            );
+            duplicates.push(Duplicate {
+                file_id: jar.file_id.clone(),
+                kept: kept.file_id.clone(),
+            });
+        } else {
+            for id in ids {
+                seen.insert(id, jar);
+            }
+        }
+    }
+    if duplicates.is_empty() {
+        return Ok(duplicates);
+    }
+    create_dir_all(directory.join(".old")).await?;
+    for duplicate in duplicates.iter() {
+        let Some(index) = resolved.iter().position(|r| r.file_id == duplicate.file_id) else {
+            continue;
+        };
+        let path = directory.join(resolved.swap_remove(index).downloadable.filename());
+        if move_file(
+            &path,
+            directory.join(".old").join(path.file_name().unwrap()),
+            &FileCopyOptions::new(),
+        )
+        .is_err()
+        {
+            remove_file(&path).await?;
+        }
+    }
+    Ok(duplicates)
+}
+
+/// Check the metadata of the jars in `resolved` in the `directory` against the `loader` and `mc_version` of the pack
+/// Mismatches, e.g. a Forge jar in a Fabric pack because of `ignore_loader`, are warned about
+pub fn validate_jars(
+    directory: &Path,
+    resolved: &[ResolvedFile],
+    loader: &ModLoader,
+    mc_version: &str,
+) {
+    for resolved in resolved
+        .iter()
+        .filter(|r| r.downloadable.filename().ends_with(".jar"))
+    {
+        let path = directory.join(resolved.downloadable.filename());
+        if !path.exists() {
+            continue;
+        }
+        let jar = match metadata::read(&path) {
+            Ok(ok) => ok,
+            Err(err) => {
+                warn!("Failed to read metadata of {:?}: {}", path, err);
+                continue;
+            }
+        };
+        let mut problems = Vec::new();
+        if !metadata::loader_supported(&jar.loaders, loader) {
+            problems.push(format!(
+                "it is made for {}, not {:?}",
+                jar.loaders.iter().map(|l| format!("{l:?}")).join(" and "),
+                loader
+            ));
+        }
+        if let (Some(minecraft), Some(format)) = (&jar.minecraft, jar.minecraft_format) {
+            if metadata::satisfies(minecraft, format, mc_version) == Some(false) {
+                problems.push(format!(
+                    "it requires Minecraft {}, not {}",
+                    minecraft, mc_version
+                ));
+            }
+        }
+        for problem in problems {
+            warn!(
+                "{} ({}) may not work: {}",
+                resolved.name, resolved.id, problem
+            );
+            output::emit(Event::Mismatch {
+                name: &resolved.name,
+                id: &resolved.id,
+                problem: &problem,
+            });
+        }
+    }
+}
+
 /// Check the `directory`
 /// If there are files that are not in `to_download`, they will be removed
 /// If a file in `to_download` is already there, it will be removed from the Vec
 /// If a file is a `.part` file or the move failed, the file will be deleted
 pub async fn clean(
     directory: &Path,
-    to_download: &mut Vec<Downloadable>,
+    to_download: &mut Vec<ResolvedFile>,
     remove: bool,
 ) -> Result<()> {
-    let dupes = find_dupes_by_key(to_download, Downloadable::filename);
+    let dupes = find_dupes_by_key(to_download, |resolved| resolved.downloadable.output.clone());
     if !dupes.is_empty() {
         info!(
             "{}",
@@ -328,38 +984,74 @@ pub async fn clean(
                 dupes.len(),
                 dupes
                     .into_iter()
-                    .map(|i| to_download.swap_remove(i).filename())
+                    .map(|i| to_download.swap_remove(i).downloadable.filename())
                     .format(", ")
             )
         );
     }
-    create_dir_all(directory.join(".old")).await?;
-    for file in read_dir(&directory)? {
+    // Only directories whose stray files are removed get an `.old` folder,
+    // in world datapack folders Minecraft would load it as a datapack
+    if remove {
+        create_dir_all(directory.join(".old")).await?;
+    }
+    for file in read_dir(directory)? {
         let file = file?;
         if file.file_type()?.is_file() {
             let filename = file.file_name();
             let filename = filename.to_str().unwrap();
-            if let Some(index) = to_download
-                .iter()
-                .position(|thing| filename == thing.filename())
-            {
+            if let Some(index) = to_download.iter().position(|thing| {
+                filename == thing.downloadable.filename()
+                    && thing
+                        .downloadable
+                        .output
+                        .parent()
+                        .is_some_and(|parent| directory.ends_with(parent))
+            }) {
                 to_download.swap_remove(index);
-            } else if filename.ends_with("part")
-                || (remove
-                    && move_file(
-                        file.path(),
-                        directory.join(".old").join(filename),
-                        &FileCopyOptions::new(),
-                    )
-                    .is_err())
-            {
+            } else if filename.ends_with("part") {
                 remove_file(file.path()).await?;
+            } else if remove {
+                if move_file(
+                    file.path(),
+                    directory.join(".old").join(filename),
+                    &FileCopyOptions::new(),
+                )
+                .is_err()
+                {
+                    remove_file(file.path()).await?;
+                }
+                output::emit(Event::Removed { path: &file.path() });
             }
         }
     }
     Ok(())
 }
 
+/// Remove the datapacks the `previous` upgrade installed into the datapack `directories` that aren't `installed` anymore
+/// Datapacks aren't moved to `.old`, since Minecraft would load everything in a datapack folder
+pub fn clean_datapacks(
+    mc_dir: &Path,
+    directories: &[PathBuf],
+    previous: &Manifest,
+    installed: &[ResolvedFile],
+) -> Result<()> {
+    for file in previous.files.iter().filter(|file| {
+        toml::is_contained(&file.path)
+            && file
+                .path
+                .parent()
+                .is_some_and(|parent| directories.iter().any(|d| d == parent))
+            && !installed.iter().any(|i| i.downloadable.output == file.path)
+    }) {
+        let path = mc_dir.join(&file.path);
+        if path.is_file() {
+            std::fs::remove_file(&path)?;
+            output::emit(Event::Removed { path: &path });
+        }
+    }
+    Ok(())
+}
+
 /// Find duplicates of the items in `slice` using a value obtained by the `key` closure
 /// Returns the indices of duplicate items in reverse order for easy removal
 fn find_dupes_by_key<T, V, F>(slice: &mut [T], key: F) -> Vec<usize>
@@ -380,3 +1072,233 @@ where
     indices.reverse();
     indices
 }
+
+#[test]
+fn dedupe_prefers_platform() {
+    let resolved = |id: ModId, filename: &str, sha1: &str| ResolvedFile {
+        name: filename.to_string(),
+        id,
+        file_id: sha1.to_string(),
+        version: String::from("1.0.0"),
+        sha1: Some(sha1.to_string()),
+        optional: Vec::new(),
+        embedded: Vec::new(),
+        incompatible: Vec::new(),
+        downloadable: Downloadable {
+            download_url: url::Url::parse("https://example.com").unwrap(),
+            output: PathBuf::from("mods").join(filename),
+            length: 0,
+        },
+    };
+    let mut to_download = vec![
+        resolved(ModId::CurseForgeId(394468), "sodium-cf.jar", "aaaa"),
+        resolved(ModId::ModrinthId("sodium".into()), "sodium-mr.jar", "aaaa"),
+        resolved(ModId::CurseForgeId(360438), "lithium.jar", "bbbb"),
+    ];
+    dedupe(&mut to_download, Platform::Modrinth);
+    let filenames: Vec<String> = to_download
+        .iter()
+        .map(|r| r.downloadable.filename())
+        .collect();
+    assert_eq!(filenames, ["sodium-mr.jar", "lithium.jar"]);
+}
+
+#[test]
+fn conflicting_pins() {
+    let (furse, ferinth) = api_clients().unwrap();
+    let resolver = Resolver {
+        furse,
+        ferinth,
+        side: ModSide::All,
+        mc_version: String::from("1.19.2"),
+        loader: ModLoader::Fabric,
+        rules: DependencyRules::default(),
+        pins: Mutex::new(HashMap::new()),
+    };
+    let dependency = |version_id: &str, required_by: &str| Mod {
+        name: format!("Dependency of {required_by}"),
+        id: ModId::ModrinthId("P7dR8mSH".into()),
+        side: ModSide::All,
+        ignore_loader: false,
+        ignore_version: false,
+        alternatives: Vec::new(),
+        pin: Some(version_id.to_string()),
+        required_by: vec![required_by.to_string()],
+        feature: None,
+        order: None,
+    };
+    let mut to_download = Vec::new();
+    let next = next_level(
+        &resolver,
+        vec![dependency("aaaa", "sodium"), dependency("aaaa", "lithium")],
+        &mut to_download,
+    )
+    .unwrap();
+    assert_eq!(next.len(), 1);
+    let err = next_level(
+        &resolver,
+        vec![dependency("bbbb", "iris")],
+        &mut to_download,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("sodium requires version aaaa"));
+    assert!(err.contains("iris requires version bbbb"));
+}
+
+#[test]
+fn incompatible_pairs() {
+    let resolved = |name: &str, incompatible: Vec<ModId>| ResolvedFile {
+        name: name.to_string(),
+        id: ModId::ModrinthId(name.to_string()),
+        file_id: name.to_string(),
+        version: String::from("1.0.0"),
+        sha1: None,
+        optional: Vec::new(),
+        embedded: Vec::new(),
+        incompatible,
+        downloadable: Downloadable {
+            download_url: url::Url::parse("https://example.com").unwrap(),
+            output: PathBuf::from("mods").join(format!("{name}.jar")),
+            length: 0,
+        },
+    };
+    let id = |name: &str| ModId::ModrinthId(name.to_string());
+    // Only optifabric declares the incompatibility, sodium and iris declare it both ways
+    let files = [
+        resolved("optifabric", vec![id("sodium")]),
+        resolved("sodium", vec![id("iris")]),
+        resolved("iris", vec![id("sodium")]),
+        resolved("lithium", Vec::new()),
+    ];
+    let names: Vec<(&str, &str)> = find_incompatibilities(&files)
+        .iter()
+        .map(|(a, b)| (a.name.as_str(), b.name.as_str()))
+        .collect();
+    assert_eq!(names, [("optifabric", "sodium"), ("sodium", "iris")]);
+    assert_eq!(
+        check_incompatibilities(&files, ConflictPolicy::Warn)
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(check_incompatibilities(&files, ConflictPolicy::Fail).is_err());
+    assert!(check_incompatibilities(&files[3..], ConflictPolicy::Fail)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn pin_keeps_entry_name() {
+    let (furse, ferinth) = api_clients().unwrap();
+    let resolver = Resolver {
+        furse,
+        ferinth,
+        side: ModSide::All,
+        mc_version: String::from("1.19.2"),
+        loader: ModLoader::Fabric,
+        rules: DependencyRules::default(),
+        pins: Mutex::new(HashMap::new()),
+    };
+    let mut to_download = vec![ResolvedFile {
+        name: String::from("sodium"),
+        id: ModId::ModrinthId("AANobbMI".into()),
+        file_id: String::from("bbbb"),
+        version: String::from("0.4.10"),
+        sha1: None,
+        optional: Vec::new(),
+        embedded: Vec::new(),
+        incompatible: Vec::new(),
+        downloadable: Downloadable {
+            download_url: url::Url::parse("https://example.com").unwrap(),
+            output: PathBuf::from("mods").join("sodium.jar"),
+            length: 0,
+        },
+    }];
+    let dependency = Mod {
+        name: String::from("Dependency of iris"),
+        id: ModId::ModrinthId("AANobbMI".into()),
+        side: ModSide::All,
+        ignore_loader: false,
+        ignore_version: false,
+        alternatives: Vec::new(),
+        pin: Some(String::from("aaaa")),
+        required_by: vec![String::from("iris")],
+        feature: None,
+        order: None,
+    };
+    let next = next_level(&resolver, vec![dependency], &mut to_download).unwrap();
+    assert!(to_download.is_empty());
+    assert_eq!(next.len(), 1);
+    assert_eq!(next[0].name, "sodium");
+    assert_eq!(next[0].pin.as_deref(), Some("aaaa"));
+}
+
+#[test]
+fn sides_from_support() {
+    use ProjectSupportRange::*;
+    assert_eq!(supported_side(&Required, &Unsupported), ModSide::Client);
+    assert_eq!(supported_side(&Unsupported, &Optional), ModSide::Server);
+    assert_eq!(supported_side(&Optional, &Required), ModSide::All);
+    assert_eq!(supported_side(&Unknown, &Unknown), ModSide::All);
+}
+
+#[test]
+fn falls_back_only_when_unavailable() {
+    let id = ModId::CurseForgeId(238222);
+    assert!(is_unavailable(
+        &BreezeError::NoCompatFile(String::from("jei"), id.clone()).into()
+    ));
+    assert!(is_unavailable(
+        &BreezeError::DistributionDenied(String::from("jei"), id).into()
+    ));
+    assert!(!is_unavailable(&anyhow::anyhow!("connection reset")));
+}
+
+#[test]
+fn removes_stale_datapacks() {
+    use crate::manifest::InstalledFile;
+    let dir =
+        std::env::temp_dir().join(format!("modbreeze-clean-datapacks-{}", std::process::id()));
+    let datapacks = PathBuf::from("saves").join("world").join("datapacks");
+    std::fs::create_dir_all(dir.join(&datapacks)).unwrap();
+    let file = |filename: &str| ResolvedFile {
+        name: filename.to_string(),
+        id: ModId::ModrinthId(filename.to_string()),
+        file_id: filename.to_string(),
+        version: String::from("1.0.0"),
+        sha1: None,
+        optional: Vec::new(),
+        embedded: Vec::new(),
+        incompatible: Vec::new(),
+        downloadable: Downloadable {
+            download_url: url::Url::parse("https://example.com").unwrap(),
+            output: datapacks.join(filename),
+            length: 0,
+        },
+    };
+    for filename in ["old.zip", "kept.zip", "dropped.zip", "user.zip"] {
+        std::fs::write(dir.join(&datapacks).join(filename), "").unwrap();
+    }
+    let previous = Manifest {
+        files: ["old.zip", "kept.zip", "dropped.zip"]
+            .map(|filename| InstalledFile::from(&file(filename)))
+            .to_vec(),
+        duplicates: Vec::new(),
+    };
+    let installed = [file("kept.zip"), file("new.zip")];
+    clean_datapacks(
+        &dir,
+        std::slice::from_ref(&datapacks),
+        &previous,
+        &installed,
+    )
+    .unwrap();
+    let mut remaining: Vec<String> = read_dir(dir.join(&datapacks))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    remaining.sort();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(remaining, ["kept.zip", "user.zip"]);
+}
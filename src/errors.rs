@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::structs::ModId;
+use std::path::PathBuf;
 
 #[derive(Error, Debug)]
 pub enum BreezeError {
@@ -8,8 +9,20 @@ pub enum BreezeError {
     InvalidLoader,
     #[error("no mods in the pack")]
     EmptyPack,
+    #[error("missing key `{0}` in the pack")]
+    MissingKey(&'static str),
+    #[error("{0:?} must be a relative path inside the Minecraft directory")]
+    UnsafePath(PathBuf),
+    #[error("pack {0} extends itself")]
+    ExtendsCycle(String),
+    #[error("expected plain text from URL response, got {0}. check the specified URL")]
+    NonPlainTextResponse(String),
     #[error("couldn't find compatible file for mod {0}, id: {1}")]
     NoCompatFile(String, ModId),
     #[error("distribution denied for mod {0}, id: {1}")]
     DistributionDenied(String, ModId),
+    #[error("no version of {0} satisfies all dependents: {1}")]
+    Unsatisfiable(ModId, String),
+    #[error("found {0} pairs of incompatible mods")]
+    Incompatible(usize),
 }
@@ -0,0 +1,285 @@
+use crate::{
+    download::{api_clients, get_project_fields, ProjectField},
+    metadata::{self, JarMetadata},
+    structs::ModId,
+};
+use anyhow::Result;
+use itertools::Itertools;
+use libium::config::structs::ModLoader;
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+
+/// A jar in the mods folder
+struct Jar {
+    filename: String,
+    sha1: String,
+    fingerprint: usize,
+    metadata: Option<JarMetadata>,
+}
+
+/// A jar that was found on Modrinth or CurseForge
+pub struct Identified {
+    pub filename: String,
+    /// The slug of the project, used as its name in the pack
+    pub name: String,
+    pub id: ModId,
+}
+
+/// The result of identifying the jars in a mods folder
+pub struct Detected {
+    pub identified: Vec<Identified>,
+    /// The filenames of the jars that weren't found on either platform
+    pub unknown: Vec<String>,
+    pub loader: Option<ModLoader>,
+    pub mc_version: Option<String>,
+}
+
+/// Identify the jars in `directory` by their hashes on Modrinth, and by their fingerprints on CurseForge
+/// The loader and Minecraft version are the ones most jars declare in their metadata
+pub async fn detect(directory: &Path) -> Result<Detected> {
+    let mut jars = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "jar") {
+            continue;
+        }
+        let bytes = std::fs::read(&path)?;
+        let metadata = match metadata::read(&path) {
+            Ok(ok) => Some(ok),
+            Err(err) => {
+                warn!("Failed to read metadata of {:?}: {}", path, err);
+                None
+            }
+        };
+        jars.push(Jar {
+            filename: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            sha1: sha1_smol::Sha1::from(&bytes).hexdigest(),
+            fingerprint: furse::cf_fingerprint(&bytes),
+            metadata,
+        });
+    }
+    jars.sort_by(|a, b| a.filename.cmp(&b.filename));
+
+    let (furse, ferinth) = api_clients()?;
+    let mut ids: HashMap<String, ModId> = HashMap::new();
+    if !jars.is_empty() {
+        let versions = ferinth
+            .get_versions_from_hashes(jars.iter().map(|jar| jar.sha1.clone()).collect())
+            .await?;
+        for jar in jars.iter() {
+            if let Some(version) = versions.get(&jar.sha1) {
+                ids.insert(
+                    jar.filename.clone(),
+                    ModId::ModrinthId(version.project_id.clone()),
+                );
+            }
+        }
+    }
+    let remaining: Vec<&Jar> = jars
+        .iter()
+        .filter(|jar| !ids.contains_key(&jar.filename))
+        .collect();
+    if !remaining.is_empty() {
+        let matches = furse
+            .get_fingerprint_matches(remaining.iter().map(|jar| jar.fingerprint).collect())
+            .await?;
+        for found in matches.exact_matches {
+            if let Some(jar) = remaining
+                .iter()
+                .find(|jar| jar.fingerprint == found.file.file_fingerprint)
+            {
+                ids.insert(jar.filename.clone(), ModId::CurseForgeId(found.id as u32));
+            }
+        }
+    }
+
+    let slugs = get_project_fields(
+        &ids.values().cloned().unique().collect::<Vec<_>>(),
+        ProjectField::Slug,
+    )
+    .await?;
+    let mut identified = Vec::new();
+    let mut unknown = Vec::new();
+    for jar in jars.iter() {
+        match ids.get(&jar.filename) {
+            Some(id) => {
+                let name = slugs.get(id).cloned().unwrap_or_else(|| match id {
+                    ModId::CurseForgeId(id) => id.to_string(),
+                    ModId::ModrinthId(id) => id.clone(),
+                });
+                info!("Identified {} as {} ({})", jar.filename, name, id);
+                identified.push(Identified {
+                    filename: jar.filename.clone(),
+                    name,
+                    id: id.clone(),
+                });
+            }
+            None => unknown.push(jar.filename.clone()),
+        }
+    }
+    let identified = identified
+        .into_iter()
+        .unique_by(|identified| identified.id.clone())
+        .collect();
+
+    let metadata: Vec<&JarMetadata> = jars.iter().filter_map(|j| j.metadata.as_ref()).collect();
+    let loader = most_common(
+        metadata
+            .iter()
+            .filter(|m| m.loaders.len() == 1)
+            .map(|m| format!("{:?}", m.loaders[0])),
+    )
+    .and_then(|loader| ModLoader::try_from(loader.to_lowercase().as_str()).ok());
+    let mc_version = most_common(
+        metadata
+            .iter()
+            .filter_map(|m| m.minecraft.as_deref().and_then(metadata::lowest_version)),
+    );
+    Ok(Detected {
+        identified,
+        unknown,
+        loader,
+        mc_version,
+    })
+}
+
+/// Get the most common of the `values`
+fn most_common(values: impl Iterator<Item = String>) -> Option<String> {
+    values
+        .counts()
+        .into_iter()
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+        .map(|(value, _)| value)
+}
+
+/// Format a TOML key, quoting it if it isn't a bare key
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+/// Generate a pack TOML named `name` listing the `detected` mods
+/// The loader and Minecraft version are commented out if they weren't detected,
+/// so the pack doesn't parse until they are filled in
+/// Mods whose names are already used get a numbered suffix
+pub fn generate_toml(name: &str, detected: &Detected) -> String {
+    let string = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let mut toml = String::new();
+    let _ = writeln!(toml, "name = {}", string(name));
+    let _ = writeln!(toml, "version = \"1.0.0\"");
+    match &detected.loader {
+        Some(loader) => {
+            let _ = writeln!(
+                toml,
+                "loader = {}",
+                string(&format!("{loader:?}").to_lowercase())
+            );
+        }
+        None => {
+            let _ = writeln!(toml, "# loader = \"forge\", \"fabric\" or \"quilt\"");
+        }
+    }
+    match &detected.mc_version {
+        Some(mc_version) => {
+            let _ = writeln!(toml, "mc_version = {}", string(mc_version));
+        }
+        None => {
+            let _ = writeln!(toml, "# mc_version = \"1.20.1\"");
+        }
+    }
+    let _ = writeln!(toml, "\n[mods.common]");
+    let mut used = HashSet::new();
+    for mod_ in detected.identified.iter() {
+        let id = match &mod_.id {
+            ModId::CurseForgeId(id) => id.to_string(),
+            ModId::ModrinthId(id) => string(id),
+        };
+        let mut key = mod_.name.clone();
+        let mut suffix = 1;
+        while !used.insert(key.clone()) {
+            suffix += 1;
+            key = format!("{}-{}", mod_.name, suffix);
+        }
+        let _ = writeln!(toml, "{} = {}", toml_key(&key), id);
+    }
+    if !detected.unknown.is_empty() {
+        let _ = writeln!(toml, "\n# Not found on Modrinth or CurseForge:");
+        for filename in detected.unknown.iter() {
+            let _ = writeln!(toml, "# {filename}");
+        }
+    }
+    toml
+}
+
+#[tokio::test]
+async fn generated_toml_parses() {
+    let detected = Detected {
+        identified: vec![
+            Identified {
+                filename: String::from("sodium.jar"),
+                name: String::from("sodium"),
+                id: ModId::ModrinthId(String::from("AANobbMI")),
+            },
+            Identified {
+                filename: String::from("jei.jar"),
+                name: String::from("jei"),
+                id: ModId::CurseForgeId(238222),
+            },
+            Identified {
+                filename: String::from("jei-modrinth.jar"),
+                name: String::from("jei"),
+                id: ModId::ModrinthId(String::from("u6dRKJwZ")),
+            },
+        ],
+        unknown: vec![String::from("custom.jar")],
+        loader: Some(ModLoader::Fabric),
+        mc_version: Some(String::from("1.20.1")),
+    };
+    let pack = crate::toml::parse(generate_toml("My \"pack\"", &detected), None)
+        .await
+        .unwrap();
+    assert_eq!(pack.name, "My \"pack\"");
+    assert_eq!(pack.mc_version, "1.20.1");
+    assert_eq!(pack.mods.len(), 3);
+    assert!(pack.mods.iter().any(|m| m.name == "jei-2"));
+    assert!(pack.mods.contains(&crate::structs::Mod {
+        name: String::from("jei"),
+        id: ModId::CurseForgeId(238222),
+        side: crate::structs::ModSide::All,
+        ignore_loader: false,
+        ignore_version: false,
+        alternatives: Vec::new(),
+        pin: None,
+        required_by: Vec::new(),
+        feature: None,
+        order: None,
+    }));
+
+    let undetected = Detected {
+        loader: None,
+        mc_version: None,
+        ..detected
+    };
+    let toml = generate_toml("pack", &undetected);
+    assert!(toml.contains("# loader = ") && toml.contains("# mc_version = "));
+    let err = crate::toml::parse(toml, None)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("missing key `loader`"), "{err}");
+}
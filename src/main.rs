@@ -1,83 +1,154 @@
 use anyhow::Result;
-use config::Config;
+use clap::Parser;
+use cli::{Cli, LogFormat};
+use config::{Config, ConfigError};
+use log::{info, warn};
 use std::{
-    io::prelude::Write,
+    io::{prelude::Write, IsTerminal},
     path::{Path, PathBuf},
+    process::ExitCode,
 };
 
+mod changelog;
 mod cli;
 mod config;
+mod diff;
 mod download;
 mod errors;
+mod init;
+mod manifest;
+mod metadata;
+mod options;
+mod output;
 mod structs;
 mod toml;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let res = actual_main().await;
     if let Err(e) = res {
-        eprintln!("{}", e);
+        if output::is_json() {
+            output::emit(output::Event::Error {
+                message: e.to_string(),
+            });
+        } else {
+            eprintln!("{}", e);
+        }
+        return ExitCode::FAILURE;
     }
+    ExitCode::SUCCESS
 }
 
 async fn actual_main() -> Result<()> {
-    let config_path = get_config_file_path()?;
-    setup_logging(&config_path, false)?;
+    let (config_path, custom) = get_config_file_path()?;
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let cli = Cli::parse();
+    output::set_format(cli.output);
+    output::set_interactive(
+        !cli.non_interactive && !output::is_json() && std::io::stdin().is_terminal(),
+    );
+    setup_logging(&config_path, &cli)?;
+    if !custom {
+        migrate_exe_config(&config_path)?;
+    }
     let mut config = if Path::new(&config_path).exists() {
         load_config(&config_path)?
     } else {
         Default::default()
     };
 
-    cli::cli(&mut config).await?;
+    cli::cli(cli, &mut config).await?;
 
     save_config(config, &config_path)?;
     Ok(())
 }
 
-fn setup_logging(path: &Path, verbose: bool) -> Result<()> {
+/// How many logs of previous runs are kept next to `latest.log`
+const KEPT_LOGS: usize = 5;
+
+fn setup_logging(path: &Path, cli: &Cli) -> Result<()> {
+    let log_dir = path.parent().unwrap_or(Path::new(""));
+    rotate_logs(log_dir)?;
+    let format = cli.log_format;
     fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
+        .format(move |out, message, record| match format {
+            LogFormat::Text => out.finish(format_args!(
                 "{}[{}][{}] {}",
                 chrono::Local::now().format("[%H:%M:%S]"),
                 record.target(),
                 record.level(),
                 message
-            ))
+            )),
+            LogFormat::Json => out.finish(format_args!(
+                "{}",
+                serde_json::json!({
+                    "time": chrono::Local::now().to_rfc3339(),
+                    "target": record.target(),
+                    "level": record.level().as_str(),
+                    "message": message.to_string(),
+                })
+            )),
+        })
+        .level(if cli.verbose > 1 {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Info
         })
-        .level(log::LevelFilter::Info)
         .chain(
             std::fs::OpenOptions::new()
                 .write(true)
                 .create(true)
                 .truncate(true)
-                .open(if let Some(parent) = path.parent() {
-                    parent.join("latest.log")
-                } else {
-                    Path::new("latest.log").to_path_buf()
-                })?,
+                .open(log_dir.join("latest.log"))?,
         )
         .chain(
             fern::Dispatch::new()
-                .level(if verbose {
-                    log::LevelFilter::Info
-                } else {
-                    log::LevelFilter::Warn
+                .level(match (cli.quiet, cli.verbose) {
+                    (true, _) => log::LevelFilter::Error,
+                    // Progress is logged when there are no spinners
+                    (false, 0) if !output::is_interactive() => log::LevelFilter::Info,
+                    (false, 0) => log::LevelFilter::Warn,
+                    (false, 1) => log::LevelFilter::Info,
+                    (false, _) => log::LevelFilter::Debug,
                 })
-                .chain(std::io::stdout()),
+                .chain(if output::is_json() {
+                    // Keep stdout for the JSON output
+                    fern::Output::from(std::io::stderr())
+                } else {
+                    fern::Output::from(std::io::stdout())
+                }),
         )
         .apply()?;
     Ok(())
 }
 
+/// Keep the last `KEPT_LOGS` logs in the `log_dir` as `latest.1.log`, `latest.2.log` and so on
+fn rotate_logs(log_dir: &Path) -> Result<()> {
+    let log = |n: usize| {
+        log_dir.join(if n == 0 {
+            String::from("latest.log")
+        } else {
+            format!("latest.{n}.log")
+        })
+    };
+    for n in (0..KEPT_LOGS).rev() {
+        if log(n).exists() {
+            std::fs::rename(log(n), log(n + 1))?;
+        }
+    }
+    Ok(())
+}
+
 fn load_config(config_path: &Path) -> Result<Config> {
     let json = std::fs::read_to_string(config_path)?;
-    let config = serde_json::from_str(&json)?;
+    let config = serde_json::from_value(config::migrate(serde_json::from_str(&json)?)?)?;
     Ok(config)
 }
 
-fn save_config(config: Config, config_path: &Path) -> Result<()> {
+fn save_config(mut config: Config, config_path: &Path) -> Result<()> {
+    config.version = config::CONFIG_VERSION;
     let ser = serde_json::to_string(&config)?;
     let mut file = std::fs::OpenOptions::new()
         .write(true)
@@ -89,13 +160,28 @@ fn save_config(config: Config, config_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn get_config_file_path() -> Result<PathBuf> {
+/// Get the path of the config file, and whether it was set with `MODBREEZE_CONFIG_PATH`
+fn get_config_file_path() -> Result<(PathBuf, bool)> {
     if let Ok(ok) = std::env::var("MODBREEZE_CONFIG_PATH") {
-        Ok(Path::new(&ok).to_path_buf())
+        Ok((Path::new(&ok).to_path_buf(), true))
     } else {
-        Ok(Path::new(&std::env::current_exe()?)
-            .parent()
-            .unwrap_or(&dirs::config_dir().unwrap().join("modbreeze"))
-            .join("config.json"))
+        let config_dir = dirs::config_dir().ok_or(ConfigError::NoConfigDir)?;
+        Ok((config_dir.join("modbreeze").join("config.json"), false))
     }
 }
+
+/// Move the config next to the executable, where older versions kept it, to the `config_path`
+fn migrate_exe_config(config_path: &Path) -> Result<()> {
+    let exe_config = std::env::current_exe()?
+        .parent()
+        .map(|parent| parent.join("config.json"));
+    if let Some(exe_config) = exe_config.filter(|c| c.exists() && !config_path.exists()) {
+        info!("Moving config from {:?} to {:?}", exe_config, config_path);
+        std::fs::copy(&exe_config, config_path)?;
+        // The directory of the executable may be read-only, the copy is used from now on either way
+        if let Err(err) = std::fs::remove_file(&exe_config) {
+            warn!("Couldn't remove the old config {:?}: {}", exe_config, err);
+        }
+    }
+    Ok(())
+}
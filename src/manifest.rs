@@ -0,0 +1,77 @@
+use crate::structs::{ModId, ResolvedFile};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The file in the Minecraft directory that lists the files installed by the last upgrade
+const MANIFEST_FILE: &str = ".modbreeze.json";
+
+/// The files installed by the last upgrade of an instance
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub files: Vec<InstalledFile>,
+    /// Jars left out because they contain the same mod as another installed jar
+    #[serde(default)]
+    pub duplicates: Vec<Duplicate>,
+}
+
+/// A file that isn't installed because the file `kept` contains the same mod
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Duplicate {
+    /// The CurseForge file ID or Modrinth version ID of the left out file
+    pub file_id: String,
+    /// The file ID of the file that is installed instead
+    pub kept: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct InstalledFile {
+    pub name: String,
+    pub id: ModId,
+    /// The CurseForge file ID or Modrinth version ID
+    pub file_id: String,
+    pub version: String,
+    /// The path of the file relative to the Minecraft directory
+    pub path: PathBuf,
+}
+
+impl Manifest {
+    /// Get the installed file of the project with the `id`
+    pub fn get(&self, id: &ModId) -> Option<&InstalledFile> {
+        self.files.iter().find(|file| &file.id == id)
+    }
+}
+
+impl From<&ResolvedFile> for InstalledFile {
+    fn from(resolved: &ResolvedFile) -> Self {
+        Self {
+            name: resolved.name.clone(),
+            id: resolved.id.clone(),
+            file_id: resolved.file_id.clone(),
+            version: resolved.version.clone(),
+            path: resolved.downloadable.output.clone(),
+        }
+    }
+}
+
+/// Read the manifest of the instance in `mc_dir`, if it was upgraded before
+pub fn read(mc_dir: &Path) -> Result<Option<Manifest>> {
+    let path = mc_dir.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+}
+
+/// Write the `installed` files and the `duplicates` left out to the manifest of the instance in `mc_dir`
+pub fn write(mc_dir: &Path, installed: &[ResolvedFile], duplicates: Vec<Duplicate>) -> Result<()> {
+    let manifest = Manifest {
+        files: installed.iter().map(InstalledFile::from).collect(),
+        duplicates,
+    };
+    std::fs::write(
+        mc_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use anyhow::Result;
+use libium::config::structs::ModLoader;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{cmp::Ordering, collections::HashMap, fs::File, io::Read, path::Path};
+use zip::{result::ZipError, ZipArchive};
+
+/// What the metadata of a jar declares
+#[derive(Debug, Default)]
+pub struct JarMetadata {
+    /// The mod IDs in the jar
+    pub ids: Vec<String>,
+    /// The loaders the jar has metadata for
+    pub loaders: Vec<ModLoader>,
+    /// The Minecraft versions the jar requires, as written in the metadata
+    pub minecraft: Option<String>,
+    /// The metadata file the Minecraft requirement was read from
+    pub minecraft_format: Option<MetadataFormat>,
+}
+
+/// The metadata files of the loaders, which write version requirements differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataFormat {
+    /// `fabric.mod.json`
+    Fabric,
+    /// `quilt.mod.json`
+    Quilt,
+    /// `META-INF/mods.toml`, which uses Maven version ranges
+    ModsToml,
+}
+
+#[derive(Deserialize)]
+struct FabricModJson {
+    id: String,
+    #[serde(default)]
+    depends: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct QuiltModJson {
+    quilt_loader: QuiltLoader,
+}
+
+#[derive(Deserialize)]
+struct QuiltLoader {
+    id: String,
+    #[serde(default)]
+    depends: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct ModsToml {
+    mods: Vec<ModsTomlMod>,
+    #[serde(default)]
+    dependencies: HashMap<String, Vec<ModsTomlDependency>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlMod {
+    mod_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlDependency {
+    mod_id: String,
+    version_range: Option<String>,
+}
+
+/// Read the file at `name` in the `archive` to a string
+/// Returns `None` if there is no such file
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(ok) => ok,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut contents = String::new();
+    entry.read_to_string(&mut contents)?;
+    Ok(Some(contents))
+}
+
+/// Join a version requirement that is either a string or a list of alternatives
+fn join_versions(value: &Value) -> Option<String> {
+    match value {
+        Value::String(version) => Some(version.clone()),
+        Value::Array(versions) => Some(
+            versions
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(" || "),
+        ),
+        _ => None,
+    }
+}
+
+/// Read the metadata of the jar at `path`
+/// Supports `fabric.mod.json`, `quilt.mod.json` and `META-INF/mods.toml`
+pub fn read(path: &Path) -> Result<JarMetadata> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut metadata = JarMetadata::default();
+    if let Some(json) = read_entry(&mut archive, "fabric.mod.json")? {
+        let fabric: FabricModJson = serde_json::from_str(&json)?;
+        metadata.ids.push(fabric.id);
+        metadata.loaders.push(ModLoader::Fabric);
+        metadata.minecraft = fabric.depends.get("minecraft").and_then(join_versions);
+        metadata.minecraft_format = metadata.minecraft.as_ref().map(|_| MetadataFormat::Fabric);
+    }
+    if let Some(json) = read_entry(&mut archive, "quilt.mod.json")? {
+        let quilt: QuiltModJson = serde_json::from_str(&json)?;
+        metadata.ids.push(quilt.quilt_loader.id);
+        metadata.loaders.push(ModLoader::Quilt);
+        let minecraft = quilt.quilt_loader.depends.iter().find_map(|dep| {
+            (dep.get("id")? == "minecraft").then(|| dep.get("versions").and_then(join_versions))?
+        });
+        if metadata.minecraft.is_none() && minecraft.is_some() {
+            metadata.minecraft = minecraft;
+            metadata.minecraft_format = Some(MetadataFormat::Quilt);
+        }
+    }
+    if let Some(toml) = read_entry(&mut archive, "META-INF/mods.toml")? {
+        let mods_toml: ModsToml = toml::from_str(&toml)?;
+        metadata.loaders.push(ModLoader::Forge);
+        let minecraft = mods_toml
+            .dependencies
+            .values()
+            .flatten()
+            .find(|dep| dep.mod_id == "minecraft")
+            .and_then(|dep| dep.version_range.clone());
+        if metadata.minecraft.is_none() && minecraft.is_some() {
+            metadata.minecraft = minecraft;
+            metadata.minecraft_format = Some(MetadataFormat::ModsToml);
+        }
+        metadata
+            .ids
+            .extend(mods_toml.mods.into_iter().map(|m| m.mod_id));
+    }
+    metadata.ids.sort_unstable();
+    metadata.ids.dedup();
+    Ok(metadata)
+}
+
+/// Get the lowest Minecraft version mentioned in a version `requirement`, e.g. `1.20.1` for `>=1.20.1 <1.21`
+pub fn lowest_version(requirement: &str) -> Option<String> {
+    requirement
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .filter(|part| part.contains('.') && part.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|part| part.trim_end_matches('.'))
+        .min_by_key(|part| {
+            part.split('.')
+                .map(|n| n.parse::<u32>().unwrap_or_default())
+                .collect::<Vec<_>>()
+        })
+        .map(str::to_string)
+}
+
+/// Parse the numeric components of a version, ignoring pre-release suffixes like `-rc1`
+/// Returns `None` for versions like snapshots that can't be compared
+fn parse_version(version: &str) -> Option<Vec<u32>> {
+    let version = version.trim().split(['-', '+']).next()?;
+    version.split('.').map(|n| n.parse().ok()).collect()
+}
+
+/// Compare two versions, treating missing components as zero
+fn compare_versions(a: &[u32], b: &[u32]) -> Ordering {
+    let len = a.len().max(b.len());
+    let pad = |v: &[u32]| {
+        let mut v = v.to_vec();
+        v.resize(len, 0);
+        v
+    };
+    pad(a).cmp(&pad(b))
+}
+
+/// Whether the `version` satisfies a single Fabric or Quilt `predicate`, e.g. `>=1.20`, `~1.20.1` or `1.20.x`
+fn satisfies_predicate(predicate: &str, version: &[u32]) -> Option<bool> {
+    let predicate = predicate.trim();
+    if predicate.is_empty() || predicate == "*" {
+        return Some(true);
+    }
+    let (operator, rest) = match predicate.find(|c: char| c.is_ascii_digit()) {
+        Some(index) => predicate.split_at(index),
+        None => return None,
+    };
+    if let Some(prefix) = rest
+        .strip_suffix(".x")
+        .or_else(|| rest.strip_suffix(".X"))
+        .or_else(|| rest.strip_suffix(".*"))
+    {
+        let prefix = parse_version(prefix)?;
+        return Some(version.starts_with(&prefix));
+    }
+    let required = parse_version(rest)?;
+    let ordering = compare_versions(version, &required);
+    Some(match operator {
+        ">=" => ordering.is_ge(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        "<" => ordering.is_lt(),
+        "=" | "" => ordering.is_eq(),
+        // Same minor version
+        "~" => ordering.is_ge() && version.get(..2) == required.get(..2),
+        // Same major version
+        "^" => ordering.is_ge() && version.first() == required.first(),
+        _ => return None,
+    })
+}
+
+/// Whether the `version` is in a Maven version range like `[1.20.1,1.21)`
+fn satisfies_maven_range(range: &str, version: &[u32]) -> Option<bool> {
+    let inclusive_start = range.starts_with('[');
+    let inclusive_end = range.ends_with(']');
+    let inner = range.get(1..range.len().saturating_sub(1))?;
+    let (start, end) = match inner.split_once(',') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        // `[1.20.1]` requires exactly that version
+        None => (inner.trim(), inner.trim()),
+    };
+    let start_ok = if start.is_empty() {
+        true
+    } else {
+        let ordering = compare_versions(version, &parse_version(start)?);
+        ordering.is_gt() || (inclusive_start && ordering.is_eq())
+    };
+    let end_ok = if end.is_empty() {
+        true
+    } else {
+        let ordering = compare_versions(version, &parse_version(end)?);
+        ordering.is_lt() || (inclusive_end && ordering.is_eq())
+    };
+    Some(start_ok && end_ok)
+}
+
+/// Whether the Minecraft `version` satisfies a version `requirement` from a metadata file of the `format`
+/// Supports the Maven ranges of `mods.toml` and the predicates of `fabric.mod.json` and `quilt.mod.json`
+/// Returns `None` if either can't be understood
+pub fn satisfies(requirement: &str, format: MetadataFormat, version: &str) -> Option<bool> {
+    let requirement = requirement.trim();
+    // A bare Maven version is a soft requirement, which any version satisfies
+    if format == MetadataFormat::ModsToml && !requirement.starts_with(['[', '(']) {
+        return Some(true);
+    }
+    let version = parse_version(version)?;
+    if requirement.starts_with(['[', '(']) {
+        // Ranges are separated by commas between the closing and opening brackets
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for (index, c) in requirement.char_indices() {
+            if c == ']' || c == ')' {
+                ranges.push(requirement[start..=index].trim_start_matches([',', ' ']));
+                start = index + 1;
+            }
+        }
+        let results: Vec<bool> = ranges
+            .into_iter()
+            .map(|range| satisfies_maven_range(range, &version))
+            .collect::<Option<_>>()?;
+        return Some(results.contains(&true));
+    }
+    let mut any = false;
+    for alternative in requirement.split("||") {
+        let results: Vec<bool> = alternative
+            .split_whitespace()
+            .map(|predicate| satisfies_predicate(predicate, &version))
+            .collect::<Option<_>>()?;
+        any |= results.iter().all(|ok| *ok);
+    }
+    Some(any)
+}
+
+/// Whether a jar with metadata for the `jar_loaders` can be loaded by the `loader`
+/// Jars without metadata for any loader, like libraries, are assumed to be loadable
+pub fn loader_supported(jar_loaders: &[ModLoader], loader: &ModLoader) -> bool {
+    jar_loaders.is_empty()
+        || jar_loaders.iter().any(|jar_loader| {
+            jar_loader == loader
+                // Quilt can load Fabric mods
+                || (*loader == ModLoader::Quilt && *jar_loader == ModLoader::Fabric)
+        })
+}
+
+#[test]
+fn version_requirements() {
+    use MetadataFormat::*;
+    assert_eq!(satisfies(">=1.20 <1.21", Fabric, "1.20.1"), Some(true));
+    assert_eq!(satisfies("~1.19.2", Quilt, "1.20.1"), Some(false));
+    assert_eq!(satisfies("1.20.x", Fabric, "1.20.1"), Some(true));
+    assert_eq!(satisfies("1.19.4 || 1.20", Fabric, "1.20"), Some(true));
+    assert_eq!(satisfies("[1.20.1,1.21)", ModsToml, "1.21"), Some(false));
+    assert_eq!(
+        satisfies("[1.18,1.19),[1.20,)", ModsToml, "1.20.4"),
+        Some(true)
+    );
+    assert_eq!(satisfies("1.20.1", ModsToml, "1.20.4"), Some(true));
+    assert_eq!(satisfies("1.20.1", Fabric, "1.20.4"), Some(false));
+    assert_eq!(satisfies("*", Fabric, "23w13a"), None);
+    assert!(!loader_supported(&[ModLoader::Forge], &ModLoader::Fabric));
+    assert!(loader_supported(&[ModLoader::Fabric], &ModLoader::Quilt));
+}
+
+#[test]
+fn lowest_versions() {
+    assert_eq!(lowest_version("~1.20.1").as_deref(), Some("1.20.1"));
+    assert_eq!(lowest_version("[1.19.2,1.20)").as_deref(), Some("1.19.2"));
+    assert_eq!(
+        lowest_version(">=1.20 || 1.19.4").as_deref(),
+        Some("1.19.4")
+    );
+    assert_eq!(lowest_version("*"), None);
+}
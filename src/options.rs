@@ -0,0 +1,172 @@
+use anyhow::Result;
+use libium::config::structs::ModLoader;
+use log::info;
+use std::path::Path;
+
+const RESOURCE_PACKS_KEY: &str = "resourcePacks:";
+
+/// Enable the resourcepacks with the `filenames` in `options.txt` in the `mc_dir`, in order
+/// The `updated` resourcepacks that were installed before are swapped for their new files where they are,
+/// the `removed` ones are disabled, and resourcepacks enabled by the user are kept where they are
+pub fn set_resource_packs(
+    mc_dir: &Path,
+    filenames: &[String],
+    updated: &[(String, String)],
+    removed: &[String],
+) -> Result<()> {
+    let path = mc_dir.join("options.txt");
+    let options = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    std::fs::write(
+        path,
+        update_resource_packs(&options, filenames, updated, removed)?,
+    )?;
+    Ok(())
+}
+
+/// Replace the `resourcePacks` list in the `options`
+/// The old filenames of the `updated` resourcepacks are replaced by the new ones and the `removed` ones are dropped,
+/// then the `filenames` are placed where the first of them was, or after all other resourcepacks if none of them were enabled
+fn update_resource_packs(
+    options: &str,
+    filenames: &[String],
+    updated: &[(String, String)],
+    removed: &[String],
+) -> Result<String> {
+    let entry = |f: &String| format!("file/{f}");
+    let managed: Vec<String> = filenames.iter().map(entry).collect();
+    let removed: Vec<String> = removed.iter().map(entry).collect();
+    let mut lines: Vec<String> = options.lines().map(str::to_string).collect();
+    let index = lines.iter().position(|l| l.starts_with(RESOURCE_PACKS_KEY));
+    let mut packs: Vec<String> = match index {
+        Some(index) => serde_json::from_str(&lines[index][RESOURCE_PACKS_KEY.len()..])?,
+        None => vec![String::from("vanilla")],
+    };
+    for pack in packs.iter_mut() {
+        if let Some((_, new)) = updated.iter().find(|(old, _)| *pack == entry(old)) {
+            *pack = entry(new);
+        }
+    }
+    packs.retain(|p| !removed.contains(p));
+    let position = packs
+        .iter()
+        .position(|p| managed.contains(p))
+        .unwrap_or(packs.len());
+    packs.retain(|p| !managed.contains(p));
+    packs.splice(position..position, managed);
+    let line = format!("{}{}", RESOURCE_PACKS_KEY, serde_json::to_string(&packs)?);
+    match index {
+        Some(index) => lines[index] = line,
+        None => lines.push(line),
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Select the shaderpack with the `filename` in the Iris config, or the Oculus config for Forge
+/// Nothing is changed if a shaderpack was already selected
+pub fn set_default_shader(mc_dir: &Path, loader: &ModLoader, filename: &str) -> Result<()> {
+    let config_dir = mc_dir.join("config");
+    let path = config_dir.join(match loader {
+        ModLoader::Forge => "oculus.properties",
+        _ => "iris.properties",
+    });
+    let properties = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    match update_shader_properties(&properties, filename) {
+        Some(updated) => {
+            info!("Selecting shaderpack {} in {:?}", filename, path);
+            std::fs::create_dir_all(config_dir)?;
+            std::fs::write(path, updated)?;
+        }
+        None => info!("A shaderpack is already selected in {:?}", path),
+    }
+    Ok(())
+}
+
+/// Set the `shaderPack` in the `properties` to the `filename` and enable shaders
+/// Returns `None` if a shaderpack was already selected
+fn update_shader_properties(properties: &str, filename: &str) -> Option<String> {
+    let value = |line: &str, key: &str| {
+        line.strip_prefix(key)
+            .map(|rest| rest.trim_start())
+            .and_then(|rest| rest.strip_prefix(['=', ':']))
+            .map(|rest| rest.trim().to_string())
+    };
+    let mut lines: Vec<String> = properties.lines().map(str::to_string).collect();
+    if lines
+        .iter()
+        .any(|l| value(l, "shaderPack").is_some_and(|v| !v.is_empty()))
+    {
+        return None;
+    }
+    lines.retain(|l| value(l, "shaderPack").is_none() && value(l, "enableShaders").is_none());
+    lines.push(format!("shaderPack={}", escape_property(filename)));
+    lines.push(String::from("enableShaders=true"));
+    Some(lines.join("\n") + "\n")
+}
+
+/// Escape the characters that have a special meaning in `.properties` values
+fn escape_property(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '\\' | '=' | ':' | '#' | '!') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[test]
+fn selects_shader_once() {
+    let updated = update_shader_properties(
+        "#Iris config\nshaderPack=\nmaxShadowRenderDistance=32\n",
+        "Complementary: v4.zip",
+    )
+    .unwrap();
+    assert_eq!(
+        updated,
+        "#Iris config\nmaxShadowRenderDistance=32\nshaderPack=Complementary\\: v4.zip\nenableShaders=true\n"
+    );
+    assert_eq!(update_shader_properties(&updated, "BSL.zip"), None);
+}
+
+#[test]
+fn keeps_user_resource_packs() {
+    let options = "version:3337\nresourcePacks:[\"vanilla\",\"file/b.zip\",\"file/user.zip\",\"file/a.zip\"]\nlang:en_us\n";
+    let updated = update_resource_packs(
+        options,
+        &[String::from("a.zip"), String::from("b.zip")],
+        &[],
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        updated,
+        "version:3337\nresourcePacks:[\"vanilla\",\"file/a.zip\",\"file/b.zip\",\"file/user.zip\"]\nlang:en_us\n"
+    );
+    assert_eq!(
+        update_resource_packs("", &[String::from("a.zip")], &[], &[]).unwrap(),
+        "resourcePacks:[\"vanilla\",\"file/a.zip\"]\n"
+    );
+    // An updated resourcepack replaces its old file in place, even without an order
+    assert_eq!(
+        update_resource_packs(
+            "resourcePacks:[\"vanilla\",\"file/a-1.0.zip\",\"file/user.zip\",\"file/b-1.0.zip\",\"file/c.zip\"]\n",
+            &[String::from("a-1.1.zip")],
+            &[
+                (String::from("a-1.0.zip"), String::from("a-1.1.zip")),
+                (String::from("b-1.0.zip"), String::from("b-1.1.zip")),
+            ],
+            &[String::from("c.zip")],
+        )
+        .unwrap(),
+        "resourcePacks:[\"vanilla\",\"file/a-1.1.zip\",\"file/user.zip\",\"file/b-1.1.zip\"]\n"
+    );
+}
@@ -0,0 +1,164 @@
+use crate::{changelog::Changelog, diff::Change, structs::ModId};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    fmt::Display,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON event per line on stdout
+    Json,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Set the format of the output, can only be set once
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// Whether the output is newline-delimited JSON
+pub fn is_json() -> bool {
+    FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+/// Set whether prompts, spinners and progress bars can be shown
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+}
+
+/// Whether prompts, spinners and progress bars can be shown
+/// Progress is logged instead otherwise
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// A result or progress update of a command
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A stage of the command started or finished
+    Progress {
+        stage: &'a str,
+        finished: bool,
+    },
+    Feature {
+        name: &'a str,
+        enabled: bool,
+        description: Option<&'a str>,
+    },
+    Resolved {
+        name: &'a str,
+        id: &'a ModId,
+        file_id: &'a str,
+        version: &'a str,
+        filename: String,
+    },
+    /// The optional and embedded dependencies of a resolved file that aren't resolved themselves
+    Optional {
+        name: &'a str,
+        id: &'a ModId,
+        optional: Vec<Dependency<'a>>,
+        embedded: Vec<Dependency<'a>>,
+    },
+    /// A file that has a newer version than the installed one
+    Outdated {
+        name: &'a str,
+        id: &'a ModId,
+        installed: Option<&'a str>,
+        available: &'a str,
+        changelog: Option<String>,
+    },
+    /// The changes of a file replaced by the upgrade
+    Changelog(&'a Changelog),
+    /// A difference between two versions of a pack
+    Diff(&'a Change),
+    /// A jar that was found on Modrinth or CurseForge
+    Identified {
+        filename: &'a str,
+        name: &'a str,
+        id: &'a ModId,
+    },
+    /// A jar that wasn't found on Modrinth or CurseForge
+    Unknown {
+        filename: &'a str,
+    },
+    /// Two resolved files that are incompatible with each other
+    Incompatible {
+        name: &'a str,
+        id: &'a ModId,
+        with: &'a str,
+        with_id: &'a ModId,
+    },
+    /// A downloaded jar whose metadata doesn't match the pack
+    Mismatch {
+        name: &'a str,
+        id: &'a ModId,
+        problem: &'a str,
+    },
+    /// A mod that couldn't be resolved
+    Failed {
+        name: &'a str,
+        error: String,
+    },
+    Added {
+        path: &'a Path,
+    },
+    Removed {
+        path: &'a Path,
+    },
+    Profile {
+        name: &'a str,
+        active: bool,
+        mc_dir: Option<&'a Path>,
+    },
+    /// The command finished successfully
+    Done {
+        files: usize,
+    },
+    /// The command failed
+    Error {
+        message: String,
+    },
+}
+
+/// A dependency listed in an `optional` event
+#[derive(Serialize)]
+pub struct Dependency<'a> {
+    pub id: &'a ModId,
+    pub name: String,
+}
+
+/// Print the `event` as JSON if the output is JSON
+pub fn emit(event: Event) {
+    if is_json() {
+        if let Ok(line) = serde_json::to_string(&event) {
+            println!("{line}");
+        }
+    }
+}
+
+/// Print the `text` if the output isn't JSON, for messages that have no event
+pub fn text(text: impl Display) {
+    if !is_json() {
+        println!("{text}");
+    }
+}
+
+/// Print the `event` as JSON, or the `text` otherwise
+pub fn print(event: Event, text: impl Display) {
+    if is_json() {
+        emit(event);
+    } else {
+        println!("{text}");
+    }
+}
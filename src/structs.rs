@@ -1,6 +1,9 @@
 use clap::{builder::PossibleValue, ValueEnum};
-use libium::config::structs::ModLoader;
+use libium::{config::structs::ModLoader, upgrade::Downloadable};
+use log::info;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct Mod {
@@ -9,6 +12,27 @@ pub struct Mod {
     pub side: ModSide,
     pub ignore_loader: bool,
     pub ignore_version: bool,
+    /// Projects to try in order when `id` can't be resolved
+    pub alternatives: Vec<ModId>,
+    /// The specific version required by a dependent
+    pub pin: Option<String>,
+    /// The chain of mods that caused this mod to be added, empty for mods in the pack
+    pub required_by: Vec<String>,
+    /// The feature the mod belongs to, `None` for mods that are always installed
+    pub feature: Option<String>,
+    /// The position of a resourcepack in the enabled resourcepacks, lowest first
+    pub order: Option<i32>,
+}
+
+impl Mod {
+    /// The name of the mod in dependency chains
+    pub fn label(&self) -> String {
+        if self.required_by.is_empty() {
+            self.name.clone()
+        } else {
+            self.id.to_string()
+        }
+    }
 }
 
 impl PartialEq for Mod {
@@ -22,8 +46,11 @@ pub enum ModSide {
     Client,
     Server,
     All,
+    /// Detected from the client and server support of the Modrinth project
+    Auto,
     Resourcepack,
     Shaderpack,
+    Datapack,
 }
 
 impl ValueEnum for ModSide {
@@ -32,16 +59,16 @@ impl ValueEnum for ModSide {
     }
 
     fn to_possible_value(&self) -> Option<PossibleValue> {
-        match &self {
-            &ModSide::All => Some(PossibleValue::new("All").aliases(["a", "common"])),
-            &ModSide::Client => Some(PossibleValue::new("Client").alias("c")),
-            &ModSide::Server => Some(PossibleValue::new("Server").alias("s")),
+        match self {
+            ModSide::All => Some(PossibleValue::new("All").aliases(["a", "common"])),
+            ModSide::Client => Some(PossibleValue::new("Client").alias("c")),
+            ModSide::Server => Some(PossibleValue::new("Server").alias("s")),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(untagged)]
 pub enum ModId {
     /// CurseForge ProjectID
@@ -50,6 +77,16 @@ pub enum ModId {
     ModrinthId(String),
 }
 
+impl ModId {
+    /// The platform the project is hosted on
+    pub fn platform(&self) -> Platform {
+        match self {
+            Self::CurseForgeId(_) => Platform::CurseForge,
+            Self::ModrinthId(_) => Platform::Modrinth,
+        }
+    }
+}
+
 impl std::fmt::Display for ModId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
@@ -60,13 +97,139 @@ impl std::fmt::Display for ModId {
     }
 }
 
+/// Pack-level rules applied to dependencies during resolution
+#[derive(Debug, Default, Clone)]
+pub struct DependencyRules {
+    /// Dependencies that are never added
+    pub exclude: Vec<ModId>,
+    /// Dependencies that are resolved from another project instead
+    pub replace: Vec<(ModId, ModId)>,
+    /// Optional dependencies that are added like required ones
+    pub include_optional: Vec<ModId>,
+}
+
+impl DependencyRules {
+    /// Apply the rules to the dependency `mod_`
+    /// Returns `None` if the dependency is excluded
+    pub fn apply(&self, mut mod_: Mod) -> Option<Mod> {
+        if self.exclude.contains(&mod_.id) {
+            info!("Excluding dependency: {}, id: {}", mod_.name, mod_.id);
+            return None;
+        }
+        if let Some((_, replacement)) = self.replace.iter().find(|(id, _)| id == &mod_.id) {
+            info!(
+                "Replacing dependency: {}, id: {} with id: {}",
+                mod_.name, mod_.id, replacement
+            );
+            mod_.id = replacement.clone();
+            // A version of the original project means nothing for the replacement
+            mod_.pin = None;
+        }
+        Some(mod_)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    CurseForge,
+    #[default]
+    Modrinth,
+}
+
+/// What to do when resolved files are incompatible with each other
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    #[default]
+    Fail,
+    Warn,
+}
+
+/// A file resolved for a `Mod`, ready to be downloaded
+#[derive(Debug, Clone)]
+pub struct ResolvedFile {
+    /// The name of the mod the file was resolved for
+    pub name: String,
+    /// The project the file was resolved from
+    pub id: ModId,
+    /// The CurseForge file ID or Modrinth version ID
+    pub file_id: String,
+    /// The display name or version number of the file
+    pub version: String,
+    /// The SHA1 hash of the file, if the platform provided one
+    pub sha1: Option<String>,
+    /// Optional dependencies of the file
+    pub optional: Vec<ModId>,
+    /// Projects embedded in the file
+    pub embedded: Vec<ModId>,
+    /// Projects the file declares itself incompatible with
+    pub incompatible: Vec<ModId>,
+    pub downloadable: Downloadable,
+}
+
+impl ResolvedFile {
+    /// The web page of the file, which shows its changelog
+    /// The `project_url` is the web page of the project, CurseForge file pages need its game and slug
+    pub fn changelog_url(&self, project_url: &str) -> String {
+        let project_url = project_url.trim_end_matches('/');
+        match &self.id {
+            ModId::CurseForgeId(_) => format!("{}/files/{}", project_url, self.file_id),
+            ModId::ModrinthId(_) => format!("{}/version/{}", project_url, self.file_id),
+        }
+    }
+}
+
+/// An optional group of mods in a `Pack` that can be enabled or disabled per instance
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub name: String,
+    pub description: Option<String>,
+    /// Whether the feature is enabled when there is no selection for it
+    pub default: bool,
+}
+
 #[derive(Debug)]
 pub struct Pack {
     pub name: String,
     pub version: String,
     pub loader: ModLoader,
     pub mc_version: String,
+    /// Which platform wins when the same mod is resolved from both
+    pub prefer: Platform,
+    pub on_incompatible: ConflictPolicy,
+    pub dependencies: DependencyRules,
     pub mods: Vec<Mod>,
     pub resourcepacks: Vec<Mod>,
     pub shaderpacks: Vec<Mod>,
+    pub datapacks: Vec<Mod>,
+    /// The folder relative to the Minecraft directory where a global datapack mod loads datapacks from
+    pub global_datapacks: Option<PathBuf>,
+    /// The name of the shaderpack to select when no shaderpack was selected by the user
+    pub default_shader: Option<String>,
+    pub features: Vec<Feature>,
+}
+
+impl Pack {
+    /// Get the names of the enabled features
+    /// The `selections` take precedence over the defaults of the features
+    pub fn enabled_features(&self, selections: &BTreeMap<String, bool>) -> Vec<String> {
+        self.features
+            .iter()
+            .filter(|f| *selections.get(&f.name).unwrap_or(&f.default))
+            .map(|f| f.name.clone())
+            .collect()
+    }
+
+    /// Remove the mods that belong to features which aren't `enabled`
+    pub fn retain_features(&mut self, enabled: &[String]) {
+        let is_enabled = |mod_: &Mod| match &mod_.feature {
+            Some(feature) => enabled.contains(feature),
+            None => true,
+        };
+        self.mods.retain(is_enabled);
+        self.resourcepacks.retain(is_enabled);
+        self.shaderpacks.retain(is_enabled);
+        self.datapacks.retain(is_enabled);
+    }
 }
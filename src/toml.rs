@@ -1,83 +1,437 @@
+use crate::config::PathOrUrl;
 use crate::errors::BreezeError;
-use crate::structs::{Mod, ModId, ModSide, Pack};
+use crate::structs::{
+    ConflictPolicy, DependencyRules, Feature, Mod, ModId, ModSide, Pack, Platform,
+};
 use anyhow::Result;
+use async_recursion::async_recursion;
 use libium::config::structs::ModLoader;
 use log::{info, warn};
 use rayon::prelude::*;
-use serde::Deserialize;
-use std::collections::HashMap;
+use reqwest::header::CONTENT_TYPE;
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{
+        InstanceType, Metadata, RootSchema, Schema, SchemaObject, StringValidation,
+        SubschemaValidation,
+    },
+    schema_for, JsonSchema,
+};
+use serde::{
+    de::{self, value::MapAccessDeserializer, MapAccess, Unexpected, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+use url::Url;
 
-#[derive(Deserialize)]
+/// A modpack definition
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 struct Data {
+    /// The name of the pack
     name: String,
+    /// The version of the pack
     version: String,
-    loader: String,
-    mc_version: String,
+    /// Path or URL of a pack to inherit from, relative to this pack
+    extends: Option<String>,
+    /// Names of inherited entries to remove
+    remove: Option<Vec<String>>,
+    /// The mod loader the pack is made for
+    #[schemars(schema_with = "loader_schema")]
+    loader: Option<String>,
+    /// The Minecraft version the pack is made for
+    mc_version: Option<String>,
+    prefer: Option<Platform>,
+    on_incompatible: Option<ConflictPolicy>,
+    dependencies: Option<Dependencies>,
+    #[serde(default)]
     mods: Mods,
     resourcepacks: Option<HashMap<String, TomlMod>>,
     shaderpacks: Option<HashMap<String, TomlMod>>,
+    datapacks: Option<HashMap<String, TomlMod>>,
+    global_datapacks: Option<PathBuf>,
+    default_shader: Option<String>,
+    features: Option<HashMap<String, TomlFeature>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct TomlFeature {
+    description: Option<String>,
+    default: Option<bool>,
+    mods: Option<Mods>,
+    resourcepacks: Option<HashMap<String, TomlMod>>,
+    shaderpacks: Option<HashMap<String, TomlMod>>,
+    datapacks: Option<HashMap<String, TomlMod>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct Dependencies {
+    exclude: Option<Vec<ModId>>,
+    replace: Option<HashMap<String, ModId>>,
+    include_optional: Option<Vec<ModId>>,
+}
+
+impl From<Dependencies> for DependencyRules {
+    fn from(dependencies: Dependencies) -> Self {
+        DependencyRules {
+            exclude: dependencies.exclude.unwrap_or_default(),
+            replace: dependencies
+                .replace
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(id, replacement)| (parse_id(id), replacement))
+                .collect(),
+            include_optional: dependencies.include_optional.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
 struct Mods {
     client: Option<HashMap<String, TomlMod>>,
     server: Option<HashMap<String, TomlMod>>,
+    #[serde(alias = "all")]
     common: Option<HashMap<String, TomlMod>>,
+    auto: Option<HashMap<String, TomlMod>>,
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
+#[derive(Debug)]
 enum TomlMod {
     Id(ModId),
-    Tabled {
-        id: ModId,
-        ignore_loader: Option<bool>,
-        ignore_version: Option<bool>,
-    },
+    Tabled(TomlModTable),
+}
+
+#[derive(Deserialize, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct TomlModTable {
+    id: ModId,
+    ignore_loader: Option<bool>,
+    ignore_version: Option<bool>,
+    #[serde(alias = "fallback")]
+    alternatives: Option<Alternatives>,
+    /// Make the mod a feature of its own that is disabled by default
+    optional: Option<bool>,
+    /// Enable the resourcepack at this position, lowest first
+    order: Option<i32>,
+}
+
+/// A mod is either a project ID or a table with the ID and its options
+impl JsonSchema for TomlMod {
+    fn schema_name() -> String {
+        String::from("TomlMod")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![
+                    gen.subschema_for::<ModId>(),
+                    gen.subschema_for::<TomlModTable>(),
+                ]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Deserialized by hand instead of as an untagged enum,
+/// so that mistakes in tables are reported instead of "data did not match any variant"
+impl<'de> Deserialize<'de> for TomlMod {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TomlModVisitor;
+
+        impl<'de> Visitor<'de> for TomlModVisitor {
+            type Value = TomlMod;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a CurseForge project ID, a Modrinth project ID or slug, or a table")
+            }
+
+            fn visit_i64<E: de::Error>(self, id: i64) -> Result<TomlMod, E> {
+                u32::try_from(id)
+                    .map(|id| TomlMod::Id(ModId::CurseForgeId(id)))
+                    .map_err(|_| E::invalid_value(Unexpected::Signed(id), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, id: &str) -> Result<TomlMod, E> {
+                Ok(TomlMod::Id(ModId::ModrinthId(id.to_string())))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<TomlMod, A::Error> {
+                TomlModTable::deserialize(MapAccessDeserializer::new(map)).map(TomlMod::Tabled)
+            }
+        }
+
+        deserializer.deserialize_any(TomlModVisitor)
+    }
+}
+
+#[derive(Deserialize, Debug, JsonSchema)]
+#[serde(untagged)]
+enum Alternatives {
+    One(ModId),
+    Many(Vec<ModId>),
+}
+
+impl From<&Alternatives> for Vec<ModId> {
+    fn from(alternatives: &Alternatives) -> Self {
+        match alternatives {
+            Alternatives::One(id) => vec![id.clone()],
+            Alternatives::Many(ids) => ids.clone(),
+        }
+    }
+}
+
+/// Loaders are matched case-insensitively, so the schema allows any case and suggests the lowercase names
+fn loader_schema(_: &mut SchemaGenerator) -> Schema {
+    const LOADERS: [&str; 3] = ["forge", "fabric", "quilt"];
+    // JSON Schema patterns have no case-insensitive flag
+    let any_case = |loader: &str| {
+        loader
+            .chars()
+            .map(|c| format!("[{}{}]", c.to_ascii_uppercase(), c))
+            .collect::<String>()
+    };
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(format!("^({})$", LOADERS.map(any_case).join("|"))),
+            ..Default::default()
+        })),
+        metadata: Some(Box::new(Metadata {
+            examples: LOADERS.map(Into::into).to_vec(),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Generate the JSON Schema of the pack format
+pub fn schema() -> RootSchema {
+    let mut schema = schema_for!(Data);
+    schema.schema.metadata().title = Some(String::from("Modbreeze pack"));
+    // Aliases aren't part of the generated schema
+    for (definition, field, alias) in [
+        ("Mods", "common", "all"),
+        ("TomlModTable", "alternatives", "fallback"),
+    ] {
+        if let Some(Schema::Object(object)) = schema.definitions.get_mut(definition) {
+            let properties = &mut object.object().properties;
+            if let Some(property) = properties.get(field).cloned() {
+                properties.insert(alias.to_string(), property);
+            }
+        }
+    }
+    schema
+}
+
+impl Data {
+    /// Merge the `parent` pack into this one
+    /// Entries of this pack replace entries of the parent with the same name, even from another side,
+    /// and entries listed in `remove` are dropped from the parent
+    fn merge(self, parent: Data) -> Data {
+        let removed = self.remove.unwrap_or_default();
+        let is_removed = |name: &str| removed.iter().any(|r| r == name);
+        let child_mods: Vec<String> = [
+            &self.mods.client,
+            &self.mods.server,
+            &self.mods.common,
+            &self.mods.auto,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|m| m.keys().cloned())
+        .collect();
+        let mod_skip = |name: &str| is_removed(name) || child_mods.iter().any(|c| c == name);
+        let dependencies = match (self.dependencies, parent.dependencies) {
+            (Some(child), Some(parent)) => Some(Dependencies {
+                exclude: inherit_list(child.exclude, parent.exclude),
+                replace: inherit(child.replace, parent.replace, |_| false),
+                include_optional: inherit_list(child.include_optional, parent.include_optional),
+            }),
+            (child, parent) => child.or(parent),
+        };
+        Data {
+            name: self.name,
+            version: self.version,
+            extends: None,
+            remove: None,
+            loader: self.loader.or(parent.loader),
+            mc_version: self.mc_version.or(parent.mc_version),
+            prefer: self.prefer.or(parent.prefer),
+            on_incompatible: self.on_incompatible.or(parent.on_incompatible),
+            dependencies,
+            mods: Mods {
+                client: inherit(self.mods.client, parent.mods.client, mod_skip),
+                server: inherit(self.mods.server, parent.mods.server, mod_skip),
+                common: inherit(self.mods.common, parent.mods.common, mod_skip),
+                auto: inherit(self.mods.auto, parent.mods.auto, mod_skip),
+            },
+            resourcepacks: inherit(self.resourcepacks, parent.resourcepacks, is_removed),
+            shaderpacks: inherit(self.shaderpacks, parent.shaderpacks, is_removed),
+            datapacks: inherit(self.datapacks, parent.datapacks, is_removed),
+            global_datapacks: self.global_datapacks.or(parent.global_datapacks),
+            default_shader: self.default_shader.or(parent.default_shader),
+            features: inherit(self.features, parent.features, is_removed),
+        }
+    }
+}
+
+/// Combine the `child` and `parent` tables, leaving out the entries of the parent that should be `skip`ped
+/// Entries of the child replace entries of the parent with the same key
+fn inherit<T>(
+    child: Option<HashMap<String, T>>,
+    parent: Option<HashMap<String, T>>,
+    skip: impl Fn(&str) -> bool,
+) -> Option<HashMap<String, T>> {
+    if child.is_none() && parent.is_none() {
+        return None;
+    }
+    let mut merged: HashMap<String, T> = parent
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(name, _)| !skip(name))
+        .collect();
+    merged.extend(child.unwrap_or_default());
+    Some(merged)
+}
+
+/// Combine the `child` and `parent` lists without duplicates
+fn inherit_list(child: Option<Vec<ModId>>, parent: Option<Vec<ModId>>) -> Option<Vec<ModId>> {
+    if child.is_none() && parent.is_none() {
+        return None;
+    }
+    let mut merged = parent.unwrap_or_default();
+    for id in child.unwrap_or_default() {
+        if !merged.contains(&id) {
+            merged.push(id);
+        }
+    }
+    Some(merged)
 }
 
 impl TryFrom<Data> for Pack {
     type Error = anyhow::Error;
 
     fn try_from(data: Data) -> Result<Self, Self::Error> {
-        let loader: ModLoader = match data.loader.to_lowercase().as_str() {
+        let loader_name = data.loader.ok_or(BreezeError::MissingKey("loader"))?;
+        let loader: ModLoader = match loader_name.to_lowercase().as_str() {
             "forge" => ModLoader::Forge,
             "fabric" => ModLoader::Fabric,
             "quilt" => ModLoader::Quilt,
             _ => return Err(BreezeError::InvalidLoader.into()),
         };
-        info!("Found loader: {}", data.loader.to_lowercase());
+        info!("Found loader: {}", loader_name.to_lowercase());
+        let mc_version = data
+            .mc_version
+            .ok_or(BreezeError::MissingKey("mc_version"))?;
 
         let mut mods: Vec<Mod> = Vec::new();
-        convert_mods(&mut mods, data.mods.client, ModSide::Client);
-        convert_mods(&mut mods, data.mods.server, ModSide::Server);
-        convert_mods(&mut mods, data.mods.common, ModSide::All);
+        convert_mods(&mut mods, data.mods.client, ModSide::Client, None);
+        convert_mods(&mut mods, data.mods.server, ModSide::Server, None);
+        convert_mods(&mut mods, data.mods.common, ModSide::All, None);
+        convert_mods(&mut mods, data.mods.auto, ModSide::Auto, None);
 
         let mut resourcepacks: Vec<Mod> = Vec::new();
         convert_mods(
             &mut resourcepacks,
             data.resourcepacks,
             ModSide::Resourcepack,
+            None,
         );
         let mut shaderpacks: Vec<Mod> = Vec::new();
-        convert_mods(&mut shaderpacks, data.shaderpacks, ModSide::Shaderpack);
+        convert_mods(
+            &mut shaderpacks,
+            data.shaderpacks,
+            ModSide::Shaderpack,
+            None,
+        );
+
+        let mut datapacks: Vec<Mod> = Vec::new();
+        convert_mods(&mut datapacks, data.datapacks, ModSide::Datapack, None);
+
+        let mut features: Vec<Feature> = Vec::new();
+        for (name, feature) in data.features.unwrap_or_default() {
+            let mods_ = feature.mods.unwrap_or_default();
+            convert_mods(&mut mods, mods_.client, ModSide::Client, Some(&name));
+            convert_mods(&mut mods, mods_.server, ModSide::Server, Some(&name));
+            convert_mods(&mut mods, mods_.common, ModSide::All, Some(&name));
+            convert_mods(&mut mods, mods_.auto, ModSide::Auto, Some(&name));
+            convert_mods(
+                &mut resourcepacks,
+                feature.resourcepacks,
+                ModSide::Resourcepack,
+                Some(&name),
+            );
+            convert_mods(
+                &mut shaderpacks,
+                feature.shaderpacks,
+                ModSide::Shaderpack,
+                Some(&name),
+            );
+            convert_mods(
+                &mut datapacks,
+                feature.datapacks,
+                ModSide::Datapack,
+                Some(&name),
+            );
+            features.push(Feature {
+                name,
+                description: feature.description,
+                default: feature.default.unwrap_or(false),
+            });
+        }
+        // Mods marked as optional are features of their own
+        for mod_ in mods
+            .iter()
+            .chain(&resourcepacks)
+            .chain(&shaderpacks)
+            .chain(&datapacks)
+        {
+            if let Some(feature) = &mod_.feature {
+                if !features.iter().any(|f| &f.name == feature) {
+                    features.push(Feature {
+                        name: feature.clone(),
+                        description: None,
+                        default: false,
+                    });
+                }
+            }
+        }
+        features.sort_unstable_by(|a, b| a.name.cmp(&b.name));
 
         // TODO: remove when Customization support is added to CurseForge API
         // disable CurseForge shaderpacks
         let filtered: Vec<Mod> = shaderpacks
             .clone()
             .into_iter()
-            .filter(|s| match s.id {
-                ModId::CurseForgeId(_) => false,
-                _ => true,
-            })
+            .filter(|s| !matches!(s.id, ModId::CurseForgeId(_)))
             .collect();
         if shaderpacks.len() != filtered.len() {
             warn!("CurseForge shaderpacks are unsupported by the CurseForge API and are disabled in modbreeze.");
             shaderpacks = filtered;
         }
 
-        if mods.is_empty() && resourcepacks.is_empty() && shaderpacks.is_empty() {
+        if let Some(global_datapacks) = &data.global_datapacks {
+            if !is_contained(global_datapacks) {
+                return Err(BreezeError::UnsafePath(global_datapacks.clone()).into());
+            }
+        }
+
+        if mods.is_empty()
+            && resourcepacks.is_empty()
+            && shaderpacks.is_empty()
+            && datapacks.is_empty()
+        {
             return Err(BreezeError::EmptyPack.into());
         }
 
@@ -85,15 +439,43 @@ impl TryFrom<Data> for Pack {
             name: data.name,
             version: data.version,
             loader,
-            mc_version: data.mc_version,
+            mc_version,
+            prefer: data.prefer.unwrap_or_default(),
+            on_incompatible: data.on_incompatible.unwrap_or_default(),
+            dependencies: data.dependencies.map(Into::into).unwrap_or_default(),
             mods,
             resourcepacks,
             shaderpacks,
+            datapacks,
+            global_datapacks: data.global_datapacks,
+            default_shader: data.default_shader,
+            features,
         })
     }
 }
 
-fn convert_mods(mods: &mut Vec<Mod>, raw: Option<HashMap<String, TomlMod>>, side: ModSide) {
+/// Whether the relative `path` stays inside the directory it is joined onto
+/// Absolute paths and `..` components are rejected, since packs can come from anywhere
+pub fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Parse a project ID used as a TOML key
+/// Numeric keys are CurseForge IDs, anything else is a Modrinth ID or slug
+fn parse_id(id: String) -> ModId {
+    match id.parse() {
+        Ok(id) => ModId::CurseForgeId(id),
+        Err(_) => ModId::ModrinthId(id),
+    }
+}
+
+fn convert_mods(
+    mods: &mut Vec<Mod>,
+    raw: Option<HashMap<String, TomlMod>>,
+    side: ModSide,
+    feature: Option<&str>,
+) {
     if raw.is_none() {
         return;
     }
@@ -102,36 +484,56 @@ fn convert_mods(mods: &mut Vec<Mod>, raw: Option<HashMap<String, TomlMod>>, side
         ModSide::All => "common",
         ModSide::Client => "client",
         ModSide::Server => "server",
+        ModSide::Auto => "auto",
         ModSide::Resourcepack => "resourcepack",
         ModSide::Shaderpack => "shaderpack",
+        ModSide::Datapack => "datapack",
     };
     let new: Vec<Mod> = raw
         .par_iter()
         .map(|(name, id)| {
-            let name = name.to_string();
-            let (id, ignore_loader, ignore_version) = match id {
-                TomlMod::Id(id) => (
-                    id,
-                    side == ModSide::Resourcepack || side == ModSide::Shaderpack,
-                    false,
-                ),
-                TomlMod::Tabled {
-                    id,
-                    ignore_loader,
-                    ignore_version,
-                } => (
-                    id,
-                    ignore_loader.unwrap_or(false),
-                    ignore_version.unwrap_or(false),
+            let mut mod_ = Mod {
+                name: name.to_string(),
+                id: match id {
+                    TomlMod::Id(id) | TomlMod::Tabled(TomlModTable { id, .. }) => id.clone(),
+                },
+                side,
+                ignore_loader: matches!(
+                    side,
+                    ModSide::Resourcepack | ModSide::Shaderpack | ModSide::Datapack
                 ),
+                ignore_version: false,
+                alternatives: Vec::new(),
+                pin: None,
+                required_by: Vec::new(),
+                feature: feature.map(str::to_string),
+                order: None,
             };
-            Mod {
-                name,
-                id: id.clone(),
-                side,
+            if let TomlMod::Tabled(TomlModTable {
                 ignore_loader,
                 ignore_version,
+                alternatives,
+                optional,
+                order,
+                ..
+            }) = id
+            {
+                mod_.ignore_loader = ignore_loader.unwrap_or(false);
+                mod_.ignore_version = ignore_version.unwrap_or(false);
+                mod_.alternatives = alternatives.as_ref().map(Vec::from).unwrap_or_default();
+                if mod_.feature.is_none() && optional.unwrap_or(false) {
+                    mod_.feature = Some(name.to_string());
+                }
+                if side == ModSide::Resourcepack {
+                    mod_.order = *order;
+                } else if order.is_some() {
+                    warn!(
+                        "Ignoring order of {}, only resourcepacks can be ordered",
+                        name
+                    );
+                }
             }
+            mod_
         })
         .filter(|mod_| {
             if mods.contains(mod_) {
@@ -142,13 +544,307 @@ fn convert_mods(mods: &mut Vec<Mod>, raw: Option<HashMap<String, TomlMod>>, side
         })
         .collect();
     for mod_ in new {
-        info!("Adding {} mod: {}, id: {}", msg, mod_.name, mod_.id);
+        match &mod_.feature {
+            Some(feature) => info!(
+                "Adding {} mod: {}, id: {}, feature: {}",
+                msg, mod_.name, mod_.id, feature
+            ),
+            None => info!("Adding {} mod: {}, id: {}", msg, mod_.name, mod_.id),
+        }
         mods.push(mod_);
     }
 }
 
+/// Read the pack TOML from the `source`
+pub async fn read(source: &PathOrUrl) -> Result<String> {
+    Ok(match source {
+        PathOrUrl::Path(path) => tokio::fs::read_to_string(path).await?,
+        PathOrUrl::Url(url) => {
+            let resp = reqwest::get(url.as_str()).await?;
+            let content_type = resp.headers().get(CONTENT_TYPE);
+            if let Some(ct) = content_type {
+                if let Ok(ct) = ct.to_str() {
+                    if !ct.contains("text/plain") {
+                        return Err(BreezeError::NonPlainTextResponse(ct.to_string()).into());
+                    }
+                }
+            }
+            resp.text().await?
+        }
+    })
+}
+
 /// Parse the given TOML string to a `Pack` struct format
-pub fn parse(toml: String) -> Result<Pack> {
-    let data: Data = toml::from_str(toml.as_str())?;
+/// If the pack extends another pack, the other pack is read relative to the `source` and merged into it
+pub async fn parse(toml: String, source: Option<&PathOrUrl>) -> Result<Pack> {
+    // The pack itself counts as visited, so a cycle back to it is found right away
+    let data = load(toml, source, &mut source.cloned().into_iter().collect()).await?;
     data.try_into()
 }
+
+/// Deserialize the given TOML string and merge the packs it extends into it
+/// `visited` holds the sources that were already loaded, to detect cycles
+#[async_recursion(?Send)]
+async fn load(
+    toml: String,
+    source: Option<&'async_recursion PathOrUrl>,
+    visited: &mut Vec<PathOrUrl>,
+) -> Result<Data> {
+    let mut data: Data = toml::from_str(toml.as_str())?;
+    if let Some(extends) = data.extends.take() {
+        let parent_source = resolve_extends(&extends, source)?;
+        if visited.contains(&parent_source) {
+            return Err(BreezeError::ExtendsCycle(extends).into());
+        }
+        visited.push(parent_source.clone());
+        let parent_toml = read(&parent_source).await?;
+        let parent = load(parent_toml, Some(&parent_source), visited).await?;
+        info!("Extending pack {}, version {}", parent.name, parent.version);
+        data = data.merge(parent);
+    }
+    Ok(data)
+}
+
+/// Get the source of the pack in `extends`, relative to the `source` of the extending pack
+fn resolve_extends(extends: &str, source: Option<&PathOrUrl>) -> Result<PathOrUrl> {
+    if let Ok(url) = Url::parse(extends) {
+        if url.scheme() == "http" || url.scheme() == "https" {
+            return Ok(PathOrUrl::Url(url));
+        }
+    }
+    Ok(match source {
+        Some(PathOrUrl::Url(url)) => PathOrUrl::Url(url.join(extends)?),
+        Some(PathOrUrl::Path(path)) => PathOrUrl::Path(std::fs::canonicalize(
+            path.parent().unwrap_or(path).join(extends),
+        )?),
+        None => PathOrUrl::Path(std::fs::canonicalize(extends)?),
+    })
+}
+
+#[tokio::test]
+async fn parse_features() {
+    use itertools::Itertools;
+    let mut pack = parse(
+        String::from(
+            r#"
+        name = "Test"
+        version = "1.0.0"
+        loader = "fabric"
+        mc_version = "1.19.2"
+
+        [mods.common]
+        lithium = "lithium"
+        [mods.client]
+        zoomify = { id = "zoomify", optional = true }
+
+        [features.minimap]
+        default = true
+        [features.minimap.mods.client]
+        xaeros = 263420
+
+        [features.shaders.shaderpacks]
+        complementary = "complementary-shaders"
+        "#,
+        ),
+        None,
+    )
+    .await
+    .unwrap();
+    let names: Vec<&str> = pack.features.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["minimap", "shaders", "zoomify"]);
+
+    let selections = [(String::from("shaders"), true)].into_iter().collect();
+    let enabled = pack.enabled_features(&selections);
+    assert_eq!(enabled, ["minimap", "shaders"]);
+    pack.retain_features(&enabled);
+    let mods: Vec<&str> = pack.mods.iter().map(|m| m.name.as_str()).sorted().collect();
+    assert_eq!(mods, ["lithium", "xaeros"]);
+    assert_eq!(pack.shaderpacks.len(), 1);
+}
+
+#[tokio::test]
+async fn parse_dependency_rules() {
+    let pack = parse(
+        String::from(
+            r#"
+        name = "Test"
+        version = "1.0.0"
+        loader = "fabric"
+        mc_version = "1.19.2"
+
+        [dependencies]
+        exclude = ["P7dR8mSH"]
+        replace = { 306612 = "qsl" }
+
+        [mods.client]
+        sodium = "sodium"
+        "#,
+        ),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        pack.dependencies.exclude,
+        [ModId::ModrinthId("P7dR8mSH".into())]
+    );
+    assert_eq!(
+        pack.dependencies.replace,
+        [(ModId::CurseForgeId(306612), ModId::ModrinthId("qsl".into()))]
+    );
+}
+
+#[tokio::test]
+async fn parse_alternatives() {
+    let pack = parse(
+        String::from(
+            r#"
+        name = "Test"
+        version = "1.0.0"
+        loader = "fabric"
+        mc_version = "1.19.2"
+
+        [mods.client]
+        one = { id = 394468, fallback = "sodium" }
+        many = { id = "lithium", alternatives = [360438, "phosphor"] }
+        "#,
+        ),
+        None,
+    )
+    .await
+    .unwrap();
+    let one = pack.mods.iter().find(|m| m.name == "one").unwrap();
+    assert_eq!(one.alternatives, [ModId::ModrinthId("sodium".into())]);
+    let many = pack.mods.iter().find(|m| m.name == "many").unwrap();
+    assert_eq!(
+        many.alternatives,
+        [
+            ModId::CurseForgeId(360438),
+            ModId::ModrinthId("phosphor".into())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn parse_extends() {
+    // Unique per process, so parallel and repeated runs don't share files
+    let dir = std::env::temp_dir().join(format!("modbreeze-parse-extends-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = std::fs::canonicalize(dir).unwrap();
+    std::fs::write(
+        dir.join("base.toml"),
+        r#"
+        name = "Base"
+        version = "1.0.0"
+        loader = "fabric"
+        mc_version = "1.19.2"
+
+        [mods.client]
+        sodium = "sodium"
+        zoomify = "zoomify"
+        [mods.common]
+        lithium = "lithium"
+        "#,
+    )
+    .unwrap();
+    let child = dir.join("child.toml");
+    std::fs::write(
+        &child,
+        r#"
+        name = "Child"
+        version = "2.0.0"
+        extends = "base.toml"
+        remove = ["zoomify"]
+
+        [mods.common]
+        sodium = "sodium"
+        "#,
+    )
+    .unwrap();
+    // The root pack is only read from disk if the cycle back to it isn't found right away
+    let cycle = dir.join("cycle.toml");
+    std::fs::write(&cycle, "not a pack").unwrap();
+    let cycle_source = PathOrUrl::Path(cycle);
+    let cycle = parse(
+        String::from("name = \"Cycle\"\nversion = \"1.0.0\"\nextends = \"cycle.toml\""),
+        Some(&cycle_source),
+    )
+    .await;
+    let source = PathOrUrl::Path(child.clone());
+    let pack = parse(std::fs::read_to_string(&child).unwrap(), Some(&source)).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let pack = pack.unwrap();
+    assert!(cycle
+        .unwrap_err()
+        .downcast_ref::<BreezeError>()
+        .is_some_and(|err| matches!(err, BreezeError::ExtendsCycle(_))));
+    assert_eq!(pack.name, "Child");
+    assert_eq!(pack.mc_version, "1.19.2");
+    assert_eq!(pack.mods.len(), 2);
+    assert!(pack
+        .mods
+        .iter()
+        .all(|m| m.side == ModSide::All && m.name != "zoomify"));
+}
+
+#[tokio::test]
+async fn strict_validation() {
+    let pack = |mods: &str| {
+        parse(
+            format!(
+                "name = \"Test\"\nversion = \"1.0.0\"\nloader = \"fabric\"\nmc_version = \"1.19.2\"\n{mods}"
+            ),
+            None,
+        )
+    };
+    let all = pack("[mods.all]\nlithium = \"gvQqBUqZ\"").await.unwrap();
+    assert_eq!(all.mods[0].side, ModSide::All);
+
+    let err = pack("[mods.both]\nlithium = \"gvQqBUqZ\"")
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("line 5") && err.contains("unknown field `both`"));
+
+    let err = pack("[mods.client]\nsodium = { id = \"AANobbMI\", ignore_loaders = true }")
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("line 6") && err.contains("unknown field `ignore_loaders`"));
+}
+
+#[test]
+fn schema_lists_pack_format() {
+    let schema = serde_json::to_value(schema()).unwrap();
+    let properties = &schema["properties"];
+    for key in [
+        "name",
+        "version",
+        "loader",
+        "mc_version",
+        "mods",
+        "resourcepacks",
+    ] {
+        assert!(properties.get(key).is_some(), "missing {key}");
+    }
+    assert_eq!(schema["additionalProperties"], false);
+    let mods = &schema["definitions"]["Mods"]["properties"];
+    assert_eq!(mods["all"], mods["common"]);
+    assert!(schema["definitions"]["TomlMod"]["anyOf"].is_array());
+    assert_eq!(
+        properties["loader"]["pattern"],
+        "^([Ff][Oo][Rr][Gg][Ee]|[Ff][Aa][Bb][Rr][Ii][Cc]|[Qq][Uu][Ii][Ll][Tt])$"
+    );
+}
+
+#[tokio::test]
+async fn unsafe_global_datapacks() {
+    for global in ["/etc", "../datapacks", "config/../../datapacks"] {
+        let toml = format!(
+            "name = \"Test\"\nversion = \"1.0.0\"\nloader = \"fabric\"\nmc_version = \"1.19.2\"\nglobal_datapacks = \"{global}\"\n[datapacks]\nterralith = \"terralith\""
+        );
+        assert!(parse(toml, None).await.is_err(), "{global} was accepted");
+    }
+    assert!(is_contained(Path::new("config/openloader/data")));
+}